@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use crate::config::Config;
+use crate::rule::Lint;
+
+// `hubhook check` の結果. warning は表示するだけで失敗にはしない
+pub fn run(path: &Path) -> bool {
+    let config = match Config::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return false;
+        }
+    };
+
+    let lints = config.rules.lint();
+    for lint in &lints {
+        eprintln!("warning: {}", describe(&config, lint));
+    }
+
+    println!(
+        "{}: {} rules in {} files, {} warnings",
+        path.display(),
+        config.rules.len(),
+        config.files.len(),
+        lints.len()
+    );
+    true
+}
+
+fn describe(config: &Config, lint: &Lint) -> String {
+    match lint {
+        Lint::EmptyQuery { index } => format!(
+            "{}: query is empty and matches every event",
+            config.rule_name(*index)
+        ),
+        Lint::EmptyExcludeQuery { index } => format!(
+            "{}: exclude_query is empty and excludes nothing",
+            config.rule_name(*index)
+        ),
+        Lint::EmptyPattern { index, location } => format!(
+            "{}: {location} is empty and never matches",
+            config.rule_name(*index)
+        ),
+        Lint::Duplicate { index, of } => format!(
+            "{}: duplicate of {}",
+            config.rule_name(*index),
+            config.rule_name(*of)
+        ),
+        Lint::Unreachable { index, field } => format!(
+            "{}: never matches because query.{field} and exclude_query.{field} are the same",
+            config.rule_name(*index)
+        ),
+    }
+}
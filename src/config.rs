@@ -0,0 +1,515 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use futures::StreamExt;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+use tracing::{debug, error, info};
+
+use crate::rule::{self, Rule, RuleSet};
+
+#[derive(Debug, Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    rule: Vec<Rule>,
+    // file か directory. 相対 path は include を書いた file からの path
+    #[serde(default)]
+    include: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub rules: RuleSet,
+    pub files: Vec<PathBuf>, // include されたものも含め読み込んだ file
+    pub dirs: Vec<PathBuf>,  // include された directory
+    ranges: Vec<std::ops::Range<usize>>, // files ごとの rule の範囲
+}
+
+// worker 間で共有し, reload 時に丸ごと差し替える
+pub type SharedConfig = ArcSwap<Config>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        reason: String,
+    },
+    IncludeCycle(PathBuf),
+    Rule {
+        path: Option<PathBuf>,
+        error: rule::Error,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io { path, error } => {
+                write!(f, "could not read config file {}: {error}", path.display())
+            }
+            Error::Parse { path, reason } => {
+                write!(f, "could not deserialize {}: {reason}", path.display())
+            }
+            Error::IncludeCycle(path) => write!(f, "include cycle at {}", path.display()),
+            Error::Rule { path, error } => {
+                if let Some(path) = path {
+                    write!(f, "{}: ", path.display())?;
+                }
+                write!(f, "{error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    // 拡張子で判定する
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    fn parse(self, text: &str) -> Result<RawConfig, String> {
+        match self {
+            Format::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// 読み込んだ file と, その file に書かれていた rule の範囲
+struct Source {
+    path: PathBuf,
+    rules: std::ops::Range<usize>,
+}
+
+#[derive(Default)]
+struct Loader {
+    sources: Vec<Source>,
+    dirs: Vec<PathBuf>,
+    rules: Vec<Rule>,
+    stack: Vec<PathBuf>, // include の循環検出用
+}
+
+impl Loader {
+    fn load_file(&mut self, path: &Path) -> Result<(), Error> {
+        let io = |error| Error::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+        // 以前は拡張子に関係なく JSON として読んでいたので, 分からなければ JSON とみなす
+        // (e.g. config.conf, 拡張子の無い secret の mount)
+        let format = Format::from_path(path).unwrap_or(Format::Json);
+        let canonical = std::fs::canonicalize(path).map_err(io)?;
+        if self.stack.contains(&canonical) {
+            return Err(Error::IncludeCycle(path.into()));
+        }
+
+        let text = std::fs::read_to_string(path).map_err(io)?;
+        let raw = format.parse(&text).map_err(|reason| Error::Parse {
+            path: path.into(),
+            reason,
+        })?;
+
+        let start = self.rules.len();
+        self.rules.extend(raw.rule);
+        self.sources.push(Source {
+            path: path.into(),
+            rules: start..self.rules.len(),
+        });
+
+        self.stack.push(canonical);
+        let base = path.parent().unwrap_or(Path::new("."));
+        for include in raw.include {
+            let include = base.join(include);
+            if include.is_dir() {
+                self.load_dir(&include)?;
+            } else {
+                self.load_file(&include)?;
+            }
+        }
+        self.stack.pop();
+
+        Ok(())
+    }
+
+    // config.d/ のような directory は名前順に読む
+    fn load_dir(&mut self, dir: &Path) -> Result<(), Error> {
+        let io = |error| Error::Io {
+            path: dir.to_path_buf(),
+            error,
+        };
+        let mut files = vec![];
+        for entry in std::fs::read_dir(dir).map_err(io)? {
+            let path = entry.map_err(io)?.path();
+            // 隠し file (editor の swap file や ConfigMap の ..data) は読まない
+            let hidden = path
+                .file_name()
+                .map_or(true, |n| n.to_string_lossy().starts_with('.'));
+            if !hidden && path.is_file() && Format::from_path(&path).is_some() {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        self.dirs.push(dir.to_path_buf());
+        for file in files {
+            self.load_file(&file)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Config, Error> {
+        let Loader {
+            sources,
+            dirs,
+            rules,
+            ..
+        } = self;
+
+        let rules = RuleSet::new(rules).map_err(|mut error| {
+            let source = match &mut error {
+                rule::Error::InvalidPattern { index, .. } => {
+                    let source = sources.iter().find(|s| s.rules.contains(index));
+                    // file 内での index にする
+                    if let Some(source) = source {
+                        *index -= source.rules.start;
+                    }
+                    source
+                }
+                rule::Error::Build { .. } => None,
+            };
+            Error::Rule {
+                path: source.map(|s| s.path.clone()),
+                error,
+            }
+        })?;
+
+        let (files, ranges) = sources.into_iter().map(|s| (s.path, s.rules)).unzip();
+        Ok(Config {
+            rules,
+            files,
+            dirs,
+            ranges,
+        })
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        // 監視する directory を決められるように親 directory を明示する
+        let path = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => path.to_path_buf(),
+            _ => Path::new(".").join(path),
+        };
+
+        let mut loader = Loader::default();
+        loader.load_file(&path)?;
+        loader.finish()
+    }
+
+    // index 番目の rule が書かれていた file と, その file 内での index
+    pub fn locate(&self, index: usize) -> Option<(&Path, usize)> {
+        self.files
+            .iter()
+            .zip(&self.ranges)
+            .find(|(_, r)| r.contains(&index))
+            .map(|(f, r)| (f.as_path(), index - r.start))
+    }
+
+    // file 名と file 内の index で rule を示す
+    pub fn rule_name(&self, index: usize) -> String {
+        let rule = &self.rules.rules()[index];
+        let at = match self.locate(index) {
+            Some((file, i)) => format!("{}: rule[{i}]", file.display()),
+            None => format!("rule[{index}]"),
+        };
+        format!(
+            "{at} (channel = {:?}, display_name = {:?})",
+            rule.channel, rule.display_name
+        )
+    }
+}
+
+// 読み込めなかった場合は古い config のまま
+pub fn reload(path: &Path, config: &SharedConfig) -> bool {
+    match Config::load(path) {
+        Ok(new) => {
+            info!(
+                "config reloaded from \"{}\" ({} rules)",
+                path.display(),
+                new.rules.len()
+            );
+            config.store(Arc::new(new));
+            true
+        }
+        Err(e) => {
+            error!("could not reload config, keeping the old one: {e}");
+            false
+        }
+    }
+}
+
+// SIGHUP と config file の変更(inotify)で reload する
+pub fn spawn_reloader(path: PathBuf, config: Arc<SharedConfig>) {
+    use actix_web::rt::signal::unix::{signal, SignalKind};
+
+    let (tx, mut rx) = futures::channel::mpsc::unbounded::<()>();
+
+    let hup_tx = tx.clone();
+    actix_web::rt::spawn(async move {
+        let mut hup = match signal(SignalKind::hangup()) {
+            Ok(hup) => hup,
+            Err(e) => {
+                error!("could not listen SIGHUP: {e}");
+                return;
+            }
+        };
+        while hup.recv().await.is_some() {
+            info!("SIGHUP received");
+            let _ = hup_tx.unbounded_send(());
+        }
+    });
+
+    actix_web::rt::spawn(async move {
+        // drop すると監視が止まる
+        let mut _watcher = watch(&config.load(), tx.clone());
+
+        while rx.next().await.is_some() {
+            // editor の保存や ConfigMap の更新では event がまとめて来るので少し待ってから読む
+            actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+            while let Ok(Some(())) = rx.try_next() {}
+
+            if reload(&path, &config) {
+                // include される file が変わっているかもしれないので張り直す
+                _watcher = watch(&config.load(), tx.clone());
+            }
+        }
+    });
+}
+
+fn watch(
+    config: &Config,
+    tx: futures::channel::mpsc::UnboundedSender<()>,
+) -> Option<RecommendedWatcher> {
+    let files = config.files.clone();
+    let dirs = config.dirs.clone();
+
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                error!("config watch error: {e}");
+                return;
+            }
+        };
+        if event.kind.is_access() {
+            return;
+        }
+
+        // Kubernetes の ConfigMap は ..data の symlink が差し替わる
+        let changed = event.paths.iter().any(|p| {
+            files.contains(p)
+                || p.parent()
+                    .map_or(false, |d| dirs.iter().any(|dir| dir == d))
+                || p.file_name()
+                    .map_or(false, |n| n.to_string_lossy().starts_with(".."))
+        });
+        if changed {
+            debug!("config file changed: {:?}", event);
+            let _ = tx.unbounded_send(());
+        }
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("could not watch config file: {e}");
+            return None;
+        }
+    };
+
+    // 置き換えで inode が変わっても追えるように directory を監視する
+    let mut watched: Vec<&Path> = config
+        .files
+        .iter()
+        .filter_map(|f| f.parent())
+        .chain(config.dirs.iter().map(|d| d.as_path()))
+        .collect();
+    watched.sort();
+    watched.dedup();
+    for dir in watched {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            error!("could not watch {}: {e}", dir.display());
+        }
+    }
+
+    Some(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmp_config(name: &str, json: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("hubhook-{}-{name}", std::process::id()));
+        std::fs::write(&path, json).unwrap();
+        path
+    }
+
+    const RULE: &str =
+        r#"{ "channel": "ch", "display_name": "hubhook", "query": { "repo": "^arkedge/" } }"#;
+
+    #[test]
+    fn reload_keeps_old_config() {
+        let path = tmp_config("reload.json", &format!(r#"{{ "rule": [{RULE}] }}"#));
+        let config = SharedConfig::from_pointee(Config::load(&path).unwrap());
+        assert_eq!(config.load().rules.len(), 1);
+
+        std::fs::write(&path, format!(r#"{{ "rule": [{RULE}, {RULE}] }}"#)).unwrap();
+        assert!(reload(&path, &config));
+        assert_eq!(config.load().rules.len(), 2);
+
+        // 壊れた JSON
+        std::fs::write(&path, r#"{ "rule": ["#).unwrap();
+        assert!(!reload(&path, &config));
+        assert_eq!(config.load().rules.len(), 2);
+
+        // regex として不正
+        let invalid =
+            r#"{ "channel": "ch", "display_name": "hubhook", "query": { "title": "(wip" } }"#;
+        std::fs::write(&path, format!(r#"{{ "rule": [{RULE}, {invalid}] }}"#)).unwrap();
+        let e = Config::load(&path).unwrap_err();
+        assert!(
+            matches!(
+                e,
+                Error::Rule {
+                    error: rule::Error::InvalidPattern { index: 1, .. },
+                    ..
+                }
+            ),
+            "{e}"
+        );
+        assert!(!reload(&path, &config));
+        assert_eq!(config.load().rules.len(), 2);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hubhook-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("config.d")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn load_yaml_toml_with_include() {
+        let dir = tmp_dir("include");
+        std::fs::write(
+            dir.join("config.yaml"),
+            r#"
+# 全体向け
+rule:
+  - channel: ch
+    display_name: all
+    query: {}
+include:
+  - config.d
+  - extra.json
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("config.d/team-a.toml"),
+            r#"
+# team A の repo
+[[rule]]
+channel = "team-a"
+display_name = "hubhook"
+[rule.query]
+repo = "^arkedge/hubhook$"
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("config.d/team-b.yml"), "rule: []\n").unwrap();
+        // 対応していない拡張子や隠し file は読まない
+        std::fs::write(dir.join("config.d/README.md"), "# rules").unwrap();
+        std::fs::write(dir.join("config.d/.team-a.toml.swp"), "").unwrap();
+        std::fs::write(dir.join("extra.json"), format!(r#"{{ "rule": [{RULE}] }}"#)).unwrap();
+
+        let config = Config::load(&dir.join("config.yaml")).unwrap();
+        assert_eq!(config.rules.len(), 3);
+        assert_eq!(
+            config.files,
+            [
+                dir.join("config.yaml"),
+                dir.join("config.d/team-a.toml"),
+                dir.join("config.d/team-b.yml"),
+                dir.join("extra.json"),
+            ]
+        );
+        assert_eq!(config.dirs, [dir.join("config.d")]);
+        assert_eq!(
+            config.locate(1),
+            Some((dir.join("config.d/team-a.toml").as_path(), 0))
+        );
+        assert_eq!(
+            config.locate(2),
+            Some((dir.join("extra.json").as_path(), 0))
+        );
+        assert_eq!(config.locate(3), None);
+
+        // 不正な pattern は file と file 内での index で報告する
+        std::fs::write(
+            dir.join("config.d/team-b.yml"),
+            "rule:\n  - channel: ch\n    display_name: ok\n    query: {}\n  - channel: ch\n    display_name: ng\n    query:\n      title: \"(wip\"\n",
+        )
+        .unwrap();
+        let e = Config::load(&dir.join("config.yaml")).unwrap_err();
+        assert!(
+            matches!(
+                &e,
+                Error::Rule {
+                    path: Some(path),
+                    error: rule::Error::InvalidPattern { index: 1, .. }
+                } if path == &dir.join("config.d/team-b.yml")
+            ),
+            "{e}"
+        );
+
+        // include の循環
+        std::fs::write(dir.join("extra.json"), r#"{ "include": ["config.yaml"] }"#).unwrap();
+        std::fs::write(dir.join("config.d/team-b.yml"), "rule: []\n").unwrap();
+        let e = Config::load(&dir.join("config.yaml")).unwrap_err();
+        assert!(matches!(e, Error::IncludeCycle(_)), "{e}");
+
+        // 拡張子が分からなければ JSON として読む
+        let e = Config::load(&dir.join("config.d/README.md")).unwrap_err();
+        assert!(matches!(e, Error::Parse { .. }), "{e}");
+        for name in ["config.conf", "config"] {
+            std::fs::write(dir.join(name), format!(r#"{{ "rule": [{RULE}] }}"#)).unwrap();
+            assert_eq!(Config::load(&dir.join(name)).unwrap().rules.len(), 1);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::error;
+
+use crate::util::now;
+
+// 処理済みの X-GitHub-Delivery を覚えておき, 再送を無視する
+// ttl を過ぎたものと capacity を超えた古いものは忘れる
+#[derive(Debug)]
+pub struct Deliveries {
+    store: Store,
+    ttl: Duration,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+enum Store {
+    Memory(Mutex<Memory>),
+    Sqlite(Mutex<rusqlite::Connection>),
+}
+
+#[derive(Debug, Default)]
+struct Memory {
+    seen: HashMap<String, u64>,
+    order: VecDeque<(u64, String)>, // 記録した順
+}
+
+pub enum Claim {
+    New(Guard),
+    Duplicate,
+}
+
+// 処理に失敗した (done を呼ばずに drop した) 場合は再送を受け付けるように消す
+// Slack への投稿が終わるまで queue の Job に持たせるので store を Arc で持つ
+#[derive(Debug)]
+pub struct Guard {
+    deliveries: Arc<Deliveries>,
+    id: String,
+    done: bool,
+}
+
+impl Guard {
+    pub fn done(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if !self.done {
+            self.deliveries.release(&self.id);
+        }
+    }
+}
+
+impl Deliveries {
+    pub fn memory(ttl: Duration, capacity: usize) -> Self {
+        Deliveries {
+            store: Store::Memory(Mutex::default()),
+            ttl,
+            capacity,
+        }
+    }
+
+    pub fn sqlite(path: &Path, ttl: Duration, capacity: usize) -> rusqlite::Result<Self> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS deliveries (
+                id TEXT PRIMARY KEY,
+                at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS deliveries_at ON deliveries (at);",
+        )?;
+        Ok(Deliveries {
+            store: Store::Sqlite(Mutex::new(conn)),
+            ttl,
+            capacity,
+        })
+    }
+
+    // 初めての delivery なら処理中として記録する
+    // store が壊れていても通知を落とさないよう, error の場合は New として扱う
+    pub fn claim(self: &Arc<Self>, id: &str) -> Claim {
+        match self.claim_at(id, now()) {
+            Ok(false) => Claim::Duplicate,
+            Ok(true) => Claim::New(Guard {
+                deliveries: self.clone(),
+                id: id.to_string(),
+                done: false,
+            }),
+            Err(e) => {
+                error!("could not record delivery {id}: {e}");
+                Claim::New(Guard {
+                    deliveries: self.clone(),
+                    id: id.to_string(),
+                    done: true,
+                })
+            }
+        }
+    }
+
+    fn claim_at(&self, id: &str, now: u64) -> rusqlite::Result<bool> {
+        let expired = now.saturating_sub(self.ttl.as_secs());
+        match &self.store {
+            Store::Memory(memory) => {
+                let mut m = memory.lock().unwrap();
+                m.evict(expired, self.capacity.saturating_sub(1));
+                if m.seen.contains_key(id) {
+                    return Ok(false);
+                }
+                m.seen.insert(id.to_string(), now);
+                m.order.push_back((now, id.to_string()));
+                Ok(true)
+            }
+            Store::Sqlite(conn) => {
+                let conn = conn.lock().unwrap();
+                conn.execute("DELETE FROM deliveries WHERE at < ?1", [expired])?;
+                let inserted = conn.execute(
+                    "INSERT OR IGNORE INTO deliveries (id, at) VALUES (?1, ?2)",
+                    rusqlite::params![id, now],
+                )?;
+                conn.execute(
+                    "DELETE FROM deliveries WHERE id IN
+                        (SELECT id FROM deliveries ORDER BY at DESC, rowid DESC LIMIT -1 OFFSET ?1)",
+                    [self.capacity],
+                )?;
+                Ok(inserted == 1)
+            }
+        }
+    }
+
+    fn release(&self, id: &str) {
+        match &self.store {
+            Store::Memory(memory) => {
+                memory.lock().unwrap().seen.remove(id);
+            }
+            Store::Sqlite(conn) => {
+                let conn = conn.lock().unwrap();
+                if let Err(e) = conn.execute("DELETE FROM deliveries WHERE id = ?1", [id]) {
+                    error!("could not release delivery {id}: {e}");
+                }
+            }
+        }
+    }
+}
+
+impl Memory {
+    fn evict(&mut self, expired: u64, capacity: usize) {
+        while let Some((at, id)) = self.order.front() {
+            if *at >= expired && self.seen.len() <= capacity {
+                break;
+            }
+            // release 後に claim し直したものは新しい方を残す
+            if self.seen.get(id) == Some(at) {
+                self.seen.remove(id);
+            }
+            self.order.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(deliveries: Deliveries) {
+        let d = &Arc::new(deliveries);
+        assert!(d.claim_at("a", 100).unwrap());
+        assert!(!d.claim_at("a", 101).unwrap());
+        assert!(d.claim_at("b", 102).unwrap());
+
+        // ttl (10s) を過ぎたら忘れる
+        assert!(d.claim_at("a", 111).unwrap());
+        assert!(!d.claim_at("b", 111).unwrap());
+
+        // capacity (3) を超えたら古いものから忘れる
+        assert!(d.claim_at("c", 112).unwrap());
+        assert!(d.claim_at("d", 113).unwrap());
+        assert!(d.claim_at("b", 113).unwrap());
+        assert!(!d.claim_at("d", 113).unwrap());
+
+        // 処理に失敗したものは再送を受け付ける
+        match d.claim("e") {
+            Claim::New(guard) => drop(guard),
+            Claim::Duplicate => panic!("e is new"),
+        }
+        match d.claim("e") {
+            Claim::New(guard) => guard.done(),
+            Claim::Duplicate => panic!("e was released"),
+        }
+        assert!(matches!(d.claim("e"), Claim::Duplicate));
+    }
+
+    #[test]
+    fn memory() {
+        check(Deliveries::memory(Duration::from_secs(10), 3));
+    }
+
+    #[test]
+    fn sqlite() {
+        let path =
+            std::env::temp_dir().join(format!("hubhook-{}-deliveries.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        check(Deliveries::sqlite(&path, Duration::from_secs(10), 3).unwrap());
+
+        // 再起動後も覚えている
+        let d = Arc::new(Deliveries::sqlite(&path, Duration::from_secs(10), 3).unwrap());
+        assert!(matches!(d.claim("e"), Claim::Duplicate));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
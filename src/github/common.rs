@@ -88,9 +88,9 @@ pub struct Repository {
     pub labels_url: Url,
     pub releases_url: Url,
     pub deployments_url: Url,
-    pub created_at: String, // 2021-10-27T05:00:55Z
+    pub created_at: Timestamp, // 2021-10-27T05:00:55Z
     pub updated_at: String,
-    pub pushed_at: String,
+    pub pushed_at: Timestamp,
     pub git_url: Url,
     pub ssh_url: String, // "git@github.com:arkedge/hubhook.git"
     pub clone_url: Url,
@@ -121,6 +121,14 @@ pub struct Repository {
     pub default_branch: String,
 }
 
+// push event の repository だけ created_at, pushed_at が UNIX time になっている
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Timestamp {
+    Unix(u64),
+    Iso8601(String),
+}
+
 #[derive(Debug, Deserialize)]
 pub struct License {
     pub key: String,
@@ -148,13 +156,15 @@ pub struct Issue {
     pub locked: bool,
     pub assignee: Option<User>,
     pub assignees: Vec<User>,
-    pub milestone: Option<()>,
+    pub milestone: Option<Milestone>,
     pub comments: usize,
     pub created_at: String,
     pub updated_at: String,
     pub closed_at: Option<String>,
+    #[serde(default)]
+    pub state_reason: Option<String>, // completed, not_planned, reopened
     pub author_association: String,
-    pub active_lock_reason: Option<()>,
+    pub active_lock_reason: Option<String>, // resolved, off-topic, too heated, spam
     pub body: Option<String>,
     pub reactions: Reactions,
     pub timeline_url: Url,
@@ -183,10 +193,10 @@ pub struct PullRequest {
     pub merge_commit_sha: Option<String>,
     pub assignee: Option<User>, // Issueと挙動が違う？
     pub assignees: Vec<User>,
-    pub requested_reviewers: Vec<User>, // TODO: teamの場合もある
-    pub requested_teams: Vec<()>,       // TODO: これは確実にteam
+    pub requested_reviewers: Vec<User>, // team は requested_teams に入る
+    pub requested_teams: Vec<Team>,
     pub labels: Vec<Label>,
-    pub milestone: Option<()>,
+    pub milestone: Option<Milestone>,
     pub draft: bool,
     pub commits_url: Url,
     pub review_comments_url: Url,
@@ -197,9 +207,9 @@ pub struct PullRequest {
     pub base: PullRequestBase,
     pub _links: PullRequestLinks,
     pub author_association: String,
-    pub auto_merge: Option<()>,
-    pub active_lock_reason: Option<()>,
-    pub merged: Option<bool>,    // nullになりようがなくない？？？
+    pub auto_merge: Option<AutoMerge>,
+    pub active_lock_reason: Option<String>, // resolved, off-topic, too heated, spam
+    pub merged: Option<bool>,               // nullになりようがなくない？？？
     pub mergeable: Option<bool>, // ref: https://github.com/octokit/webhooks/blob/ce6ab8f2ca6c8358a415448f71e20d1d50d458f8/payload-schemas/api.github.com/common/pull-request.schema.json#L168-L170
     pub rebaseable: Option<bool>,
     pub mergeable_state: String,
@@ -213,6 +223,47 @@ pub struct PullRequest {
     pub changed_files: usize,
 }
 
+// pull_request_review などに含まれる、merge 関連の情報を持たない PullRequest
+#[derive(Debug, Deserialize)]
+pub struct SimplePullRequest {
+    pub url: Url,
+    pub id: usize,
+    pub node_id: String,
+    pub html_url: Url,
+    pub diff_url: Url,
+    pub patch_url: Url,
+    pub issue_url: Url,
+    pub number: usize,
+    pub state: String,
+    pub locked: bool,
+    pub title: String,
+    pub user: User,
+    pub body: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub closed_at: Option<String>,
+    pub merged_at: Option<String>,
+    pub merge_commit_sha: Option<String>,
+    pub assignee: Option<User>,
+    pub assignees: Vec<User>,
+    pub requested_reviewers: Vec<User>,
+    pub requested_teams: Vec<Team>,
+    pub labels: Vec<Label>,
+    pub milestone: Option<Milestone>,
+    pub draft: bool,
+    pub commits_url: Url,
+    pub review_comments_url: Url,
+    pub review_comment_url: Url,
+    pub comments_url: Url,
+    pub statuses_url: Url,
+    pub head: PullRequestHead,
+    pub base: PullRequestBase,
+    pub _links: PullRequestLinks,
+    pub author_association: String,
+    pub auto_merge: Option<AutoMerge>,
+    pub active_lock_reason: Option<String>, // resolved, off-topic, too heated, spam
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PullRequestHead {
     pub label: String,
@@ -238,6 +289,14 @@ pub struct PullRequestLinks {
     // TODO
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AutoMerge {
+    pub enabled_by: Option<User>,
+    pub merge_method: String, // merge, squash, rebase
+    pub commit_title: Option<String>,
+    pub commit_message: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct IssueComment {
     pub url: Url,
@@ -254,6 +313,225 @@ pub struct IssueComment {
     pub performed_via_github_app: Option<()>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Review {
+    pub id: usize,
+    pub node_id: String,
+    pub user: User,
+    pub body: Option<String>,
+    pub commit_id: String,
+    pub submitted_at: Option<String>,
+    pub state: ReviewState,
+    pub html_url: Url,
+    pub pull_request_url: Url,
+    pub author_association: String,
+}
+
+// Pull Request の diff に対する inline comment
+#[derive(Debug, Deserialize)]
+pub struct ReviewComment {
+    pub url: Url,
+    pub pull_request_review_id: Option<usize>,
+    pub id: usize,
+    pub node_id: String,
+    pub diff_hunk: String,
+    pub path: String,
+    pub commit_id: String,
+    pub original_commit_id: String,
+    pub user: User,
+    pub body: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub html_url: Url,
+    pub pull_request_url: Url,
+    pub author_association: String,
+    pub start_line: Option<usize>,
+    pub original_start_line: Option<usize>,
+    pub line: Option<usize>, // outdated になると null
+    pub original_line: Option<usize>,
+    pub in_reply_to_id: Option<usize>,
+    pub reactions: Reactions,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewState {
+    Approved,
+    ChangesRequested,
+    Commented,
+    Dismissed,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub url: Url,
+    pub html_url: Url,
+    pub id: usize,
+    pub node_id: String,
+    pub author: User,
+    pub tag_name: String,
+    pub target_commitish: String,
+    pub name: Option<String>,
+    pub draft: bool,
+    pub prerelease: bool,
+    pub created_at: String,
+    pub published_at: Option<String>,
+    pub tarball_url: Option<Url>,
+    pub zipball_url: Option<Url>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Discussion {
+    pub repository_url: Url,
+    pub category: DiscussionCategory,
+    pub answer_html_url: Option<Url>,
+    pub answer_chosen_at: Option<String>,
+    pub answer_chosen_by: Option<User>,
+    pub html_url: Url,
+    pub id: usize,
+    pub node_id: String,
+    pub number: usize,
+    pub title: String,
+    pub user: User,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    pub state: String,
+    pub locked: bool,
+    pub comments: usize,
+    pub created_at: String,
+    pub updated_at: String,
+    pub author_association: String,
+    pub active_lock_reason: Option<String>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscussionCategory {
+    pub id: usize,
+    pub node_id: String,
+    pub emoji: String, // ":bulb:"
+    pub name: String,
+    pub description: Option<String>,
+    pub slug: String,
+    pub is_answerable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscussionComment {
+    pub id: usize,
+    pub node_id: String,
+    pub html_url: Url,
+    pub parent_id: Option<usize>, // 返信の場合
+    pub child_comment_count: usize,
+    pub discussion_id: usize,
+    pub author_association: String,
+    pub user: User,
+    pub created_at: String,
+    pub updated_at: String,
+    pub body: String,
+}
+
+impl Discussion {
+    pub fn is_answered(&self) -> bool {
+        self.answer_html_url.is_some()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkflowRun {
+    pub id: usize,
+    pub name: Option<String>,
+    pub node_id: String,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub path: String,
+    pub display_title: String,
+    pub run_number: usize,
+    pub event: String,
+    pub status: Option<String>,
+    pub conclusion: Option<Conclusion>,
+    pub workflow_id: usize,
+    pub url: Url,
+    pub html_url: Url,
+    pub created_at: String,
+    pub updated_at: String,
+    pub actor: Option<User>,
+    pub triggering_actor: Option<User>,
+    pub run_attempt: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Workflow {
+    pub id: usize,
+    pub node_id: String,
+    pub name: String,
+    pub path: String,
+    pub state: String,
+    pub url: Url,
+    pub html_url: Url,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckSuite {
+    pub id: usize,
+    pub node_id: String,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub status: Option<String>,
+    pub conclusion: Option<Conclusion>,
+    pub url: Url,
+    pub app: App,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct App {
+    pub id: usize,
+    pub slug: Option<String>,
+    pub node_id: String,
+    pub name: String,
+    pub html_url: Url,
+}
+
+// workflow_run, check_suite の結果
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conclusion {
+    Success,
+    Failure,
+    Neutral,
+    Cancelled,
+    Skipped,
+    TimedOut,
+    ActionRequired,
+    Stale,
+    StartupFailure,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub id: String, // sha
+    pub tree_id: String,
+    pub distinct: bool,
+    pub message: String,
+    pub timestamp: String,
+    pub url: Url,
+    pub author: CommitUser,
+    pub committer: CommitUser,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitUser {
+    pub name: String,
+    pub email: Option<String>,
+    pub username: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Label {
     pub id: usize,
@@ -265,6 +543,39 @@ pub struct Label {
     pub description: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Team {
+    pub id: usize,
+    pub node_id: String,
+    pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
+    pub privacy: Option<String>,
+    pub url: Url,
+    pub html_url: Url,
+    pub permission: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Milestone {
+    pub url: Url,
+    pub html_url: Url,
+    pub labels_url: Url,
+    pub id: usize,
+    pub node_id: String,
+    pub number: usize,
+    pub title: String,
+    pub description: Option<String>,
+    pub creator: Option<User>,
+    pub open_issues: usize,
+    pub closed_issues: usize,
+    pub state: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub due_on: Option<String>,
+    pub closed_at: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Reactions {
     pub url: Url,
@@ -299,8 +610,37 @@ impl<'a> From<&'a Label> for &'a str {
     }
 }
 
-impl ToString for &Label {
-    fn to_string(&self) -> String {
-        self.name.to_string()
+impl std::fmt::Display for ReviewState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            ReviewState::Approved => "approved",
+            ReviewState::ChangesRequested => "changes_requested",
+            ReviewState::Commented => "commented",
+            ReviewState::Dismissed => "dismissed",
+        };
+        f.write_str(state)
+    }
+}
+
+impl std::fmt::Display for Conclusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let conclusion = match self {
+            Conclusion::Success => "success",
+            Conclusion::Failure => "failure",
+            Conclusion::Neutral => "neutral",
+            Conclusion::Cancelled => "cancelled",
+            Conclusion::Skipped => "skipped",
+            Conclusion::TimedOut => "timed_out",
+            Conclusion::ActionRequired => "action_required",
+            Conclusion::Stale => "stale",
+            Conclusion::StartupFailure => "startup_failure",
+        };
+        f.write_str(conclusion)
+    }
+}
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
     }
 }
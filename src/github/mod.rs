@@ -1,32 +1,194 @@
 pub mod common;
 
-use std::collections::HashMap;
-
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
+// X-GitHub-Event ごとに決まった型で deserialize する
+#[derive(Debug)]
 pub enum Payload {
     IssueComment(Box<IssueComment>),
     Issues(Box<Issues>),
     PullRequest(Box<PullRequest>),
+    PullRequestReview(Box<PullRequestReview>),
+    PullRequestReviewComment(Box<PullRequestReviewComment>),
+    Push(Box<Push>),
+    Release(Box<Release>),
+    Create(Box<Create>),
+    Delete(Box<Delete>),
+    WorkflowRun(Box<WorkflowRun>),
+    CheckSuite(Box<CheckSuite>),
+    Discussion(Box<Discussion>),
+    DiscussionComment(Box<DiscussionComment>),
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Issues {
     pub action: IssuesAction,
     pub issue: common::Issue,
+    pub label: Option<common::Label>,         // labeled, unlabeled
+    pub milestone: Option<common::Milestone>, // milestoned, demilestoned
+    pub changes: Option<IssuesChanges>,
     pub repository: common::Repository,
     pub organization: common::Organization,
     pub sender: common::User,
     pub installation: common::InstallationLite,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct IssuesChanges {
+    // transferred
+    pub new_issue: Option<common::Issue>,
+    pub new_repository: Option<common::Repository>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PullRequest {
     pub action: PullRequestAction,
+    #[allow(dead_code)]
     number: Option<usize>, // あったりなかったりする？
     pub pull_request: common::PullRequest,
+    pub requested_reviewer: Option<common::User>, // review_requested, review_request_removed
+    pub requested_team: Option<common::Team>,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#pull_request_review
+#[derive(Debug, Deserialize)]
+pub struct PullRequestReview {
+    pub action: PullRequestReviewAction,
+    pub review: common::Review,
+    pub pull_request: common::SimplePullRequest,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#pull_request_review_comment
+#[derive(Debug, Deserialize)]
+pub struct PullRequestReviewComment {
+    pub action: PullRequestReviewCommentAction,
+    pub comment: common::ReviewComment,
+    pub pull_request: common::SimplePullRequest,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#push
+#[derive(Debug, Deserialize)]
+pub struct Push {
+    #[serde(rename = "ref")]
+    pub ref_: String, // refs/heads/main, refs/tags/v1.0.0
+    pub before: String,
+    pub after: String,
+    pub created: bool,
+    pub deleted: bool,
+    pub forced: bool,
+    pub base_ref: Option<String>,
+    pub compare: url::Url,
+    pub commits: Vec<common::Commit>,
+    pub head_commit: Option<common::Commit>,
+    pub pusher: common::CommitUser,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+impl Push {
+    pub fn branch(&self) -> Option<&str> {
+        self.ref_.strip_prefix("refs/heads/")
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.ref_.strip_prefix("refs/tags/")
+    }
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#release
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub action: ReleaseAction,
+    pub release: common::Release,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#create
+#[derive(Debug, Deserialize)]
+pub struct Create {
+    #[serde(rename = "ref")]
+    pub ref_: String, // refs/ は付かない
+    pub ref_type: RefType,
+    pub master_branch: String,
+    pub description: Option<String>,
+    pub pusher_type: String,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#delete
+#[derive(Debug, Deserialize)]
+pub struct Delete {
+    #[serde(rename = "ref")]
+    pub ref_: String,
+    pub ref_type: RefType,
+    pub pusher_type: String,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#workflow_run
+#[derive(Debug, Deserialize)]
+pub struct WorkflowRun {
+    pub action: WorkflowRunAction,
+    pub workflow_run: common::WorkflowRun,
+    pub workflow: common::Workflow,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#check_suite
+#[derive(Debug, Deserialize)]
+pub struct CheckSuite {
+    pub action: CheckSuiteAction,
+    pub check_suite: common::CheckSuite,
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#discussion
+#[derive(Debug, Deserialize)]
+pub struct Discussion {
+    pub action: DiscussionAction,
+    pub discussion: common::Discussion,
+    pub answer: Option<common::DiscussionComment>, // answered
+    pub repository: common::Repository,
+    pub organization: common::Organization,
+    pub sender: common::User,
+    pub installation: common::InstallationLite,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#discussion_comment
+#[derive(Debug, Deserialize)]
+pub struct DiscussionComment {
+    pub action: DiscussionCommentAction,
+    pub comment: common::DiscussionComment,
+    pub discussion: common::Discussion,
     pub repository: common::Repository,
     pub organization: common::Organization,
     pub sender: common::User,
@@ -70,11 +232,14 @@ pub enum IssuesAction {
     Transferred,
     Milestoned,
     Demilestoned,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
 }
 
 // https://docs.github.com/ja/developers/webhooks-and-events/webhooks/webhook-events-and-payloads#pull_request
 #[derive(Debug, PartialEq, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "snake_case")]
 pub enum PullRequestAction {
     Assigned,
     AutoMergeDisabled,
@@ -93,6 +258,109 @@ pub enum PullRequestAction {
     Unassigned,
     Unlabeled,
     Unlocked,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PullRequestReviewAction {
+    Submitted,
+    Edited,
+    Dismissed,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PullRequestReviewCommentAction {
+    Created,
+    Edited,
+    Deleted,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseAction {
+    Published,
+    Unpublished,
+    Created,
+    Edited,
+    Deleted,
+    Prereleased,
+    Released,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscussionAction {
+    Created,
+    Edited,
+    Deleted,
+    Pinned,
+    Unpinned,
+    Locked,
+    Unlocked,
+    Transferred,
+    CategoryChanged,
+    Answered,
+    Unanswered,
+    Labeled,
+    Unlabeled,
+    Closed,
+    Reopened,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscussionCommentAction {
+    Created,
+    Edited,
+    Deleted,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowRunAction {
+    Requested,
+    InProgress,
+    Completed,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckSuiteAction {
+    Requested,
+    Rerequested,
+    Completed,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RefType {
+    Tag,
+    Branch,
 }
 
 #[derive(Debug, PartialEq, Deserialize)]
@@ -101,15 +369,54 @@ pub enum IssueCommentAction {
     Created,
     Edited,
     Deleted,
+    // GitHub が後から足した action など. 受け取って無視する
+    #[serde(other)]
+    Unknown,
 }
 
-use crate::{Rule, RuleMatchResult};
+// label を持たない event 用
+static NO_LABELS: Vec<common::Label> = Vec::new();
+
 impl Payload {
+    // event は X-GitHub-Event ヘッダの値. hubhook が扱わない event は None
+    pub fn from_event(event: &str, body: &[u8]) -> Result<Option<Self>, serde_json::Error> {
+        let payload = match event {
+            "issues" => Payload::Issues(serde_json::from_slice(body)?),
+            "issue_comment" => Payload::IssueComment(serde_json::from_slice(body)?),
+            "pull_request" => Payload::PullRequest(serde_json::from_slice(body)?),
+            "pull_request_review" => Payload::PullRequestReview(serde_json::from_slice(body)?),
+            "pull_request_review_comment" => {
+                Payload::PullRequestReviewComment(serde_json::from_slice(body)?)
+            }
+            "push" => Payload::Push(serde_json::from_slice(body)?),
+            "release" => Payload::Release(serde_json::from_slice(body)?),
+            "create" => Payload::Create(serde_json::from_slice(body)?),
+            "delete" => Payload::Delete(serde_json::from_slice(body)?),
+            "workflow_run" => Payload::WorkflowRun(serde_json::from_slice(body)?),
+            "check_suite" => Payload::CheckSuite(serde_json::from_slice(body)?),
+            "discussion" => Payload::Discussion(serde_json::from_slice(body)?),
+            "discussion_comment" => Payload::DiscussionComment(serde_json::from_slice(body)?),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(payload))
+    }
+
     pub fn repo(&self) -> &common::Repository {
         match &self {
             Payload::Issues(issues) => &issues.repository,
             Payload::IssueComment(icomment) => &icomment.repository,
             Payload::PullRequest(pr) => &pr.repository,
+            Payload::PullRequestReview(review) => &review.repository,
+            Payload::PullRequestReviewComment(comment) => &comment.repository,
+            Payload::Push(push) => &push.repository,
+            Payload::Release(release) => &release.repository,
+            Payload::Create(create) => &create.repository,
+            Payload::Delete(delete) => &delete.repository,
+            Payload::WorkflowRun(run) => &run.repository,
+            Payload::CheckSuite(suite) => &suite.repository,
+            Payload::Discussion(discussion) => &discussion.repository,
+            Payload::DiscussionComment(dcomment) => &dcomment.repository,
         }
     }
 
@@ -118,6 +425,16 @@ impl Payload {
             Payload::Issues(issues) => &issues.sender,
             Payload::IssueComment(icomment) => &icomment.sender,
             Payload::PullRequest(pr) => &pr.sender,
+            Payload::PullRequestReview(review) => &review.sender,
+            Payload::PullRequestReviewComment(comment) => &comment.sender,
+            Payload::Push(push) => &push.sender,
+            Payload::Release(release) => &release.sender,
+            Payload::Create(create) => &create.sender,
+            Payload::Delete(delete) => &delete.sender,
+            Payload::WorkflowRun(run) => &run.sender,
+            Payload::CheckSuite(suite) => &suite.sender,
+            Payload::Discussion(discussion) => &discussion.sender,
+            Payload::DiscussionComment(dcomment) => &dcomment.sender,
         }
     }
 
@@ -126,6 +443,15 @@ impl Payload {
             Payload::Issues(issues) => &issues.issue.title,
             Payload::IssueComment(icomment) => &icomment.issue.title,
             Payload::PullRequest(pr) => &pr.pull_request.title,
+            Payload::PullRequestReview(review) => &review.pull_request.title,
+            Payload::PullRequestReviewComment(comment) => &comment.pull_request.title,
+            Payload::Push(_) => "",
+            Payload::Release(release) => release.release.name.as_deref().unwrap_or(""),
+            Payload::Create(_) | Payload::Delete(_) => "",
+            Payload::WorkflowRun(run) => &run.workflow_run.display_title,
+            Payload::CheckSuite(_) => "",
+            Payload::Discussion(discussion) => &discussion.discussion.title,
+            Payload::DiscussionComment(dcomment) => &dcomment.discussion.title,
         }
     }
 
@@ -141,6 +467,14 @@ impl Payload {
 
             Payload::IssueComment(icomment) => &icomment.comment.body,
             Payload::PullRequest(pr) => &pr.pull_request.body,
+            Payload::PullRequestReview(review) => review.review.body.as_deref().unwrap_or(""),
+            Payload::PullRequestReviewComment(comment) => &comment.comment.body,
+            Payload::Push(_) => "",
+            Payload::Release(release) => release.release.body.as_deref().unwrap_or(""),
+            Payload::Create(_) | Payload::Delete(_) => "",
+            Payload::WorkflowRun(_) | Payload::CheckSuite(_) => "",
+            Payload::Discussion(discussion) => discussion.discussion.body.as_deref().unwrap_or(""),
+            Payload::DiscussionComment(dcomment) => &dcomment.comment.body,
         }
     }
 
@@ -149,6 +483,13 @@ impl Payload {
             Payload::Issues(issues) => &issues.issue.labels,
             Payload::IssueComment(icomment) => &icomment.issue.labels,
             Payload::PullRequest(pr) => &pr.pull_request.labels,
+            Payload::PullRequestReview(review) => &review.pull_request.labels,
+            Payload::PullRequestReviewComment(comment) => &comment.pull_request.labels,
+            Payload::Push(_) => &NO_LABELS,
+            Payload::Release(_) | Payload::Create(_) | Payload::Delete(_) => &NO_LABELS,
+            Payload::WorkflowRun(_) | Payload::CheckSuite(_) => &NO_LABELS,
+            Payload::Discussion(discussion) => &discussion.discussion.labels,
+            Payload::DiscussionComment(dcomment) => &dcomment.discussion.labels,
         }
     }
 
@@ -157,77 +498,234 @@ impl Payload {
             Payload::Issues(issues) => &issues.issue.url,
             Payload::IssueComment(icomment) => &icomment.comment.url,
             Payload::PullRequest(pr) => &pr.pull_request.url,
+            Payload::PullRequestReview(review) => &review.review.html_url,
+            Payload::PullRequestReviewComment(comment) => &comment.comment.url,
+            Payload::Push(push) => &push.compare,
+            Payload::Release(release) => &release.release.html_url,
+            Payload::Create(create) => &create.repository.html_url,
+            Payload::Delete(delete) => &delete.repository.html_url,
+            Payload::WorkflowRun(run) => &run.workflow_run.html_url,
+            Payload::CheckSuite(suite) => &suite.check_suite.url,
+            Payload::Discussion(discussion) => &discussion.discussion.html_url,
+            Payload::DiscussionComment(dcomment) => &dcomment.comment.html_url,
         }
     }
 
-    pub fn match_rules(&self, rules: &[Rule]) -> HashMap<String, RuleMatchResult> {
-        let mut v = HashMap::<String, RuleMatchResult>::new();
+    pub fn review_state(&self) -> Option<&common::ReviewState> {
+        match &self {
+            Payload::PullRequestReview(review) => Some(&review.review.state),
+            _ => None,
+        }
+    }
 
-        for r in rules {
-            // not match
-            if !r.check_match(self) {
-                continue;
-            }
+    // push 先, または Pull Request の base branch
+    pub fn branch(&self) -> Option<&str> {
+        match &self {
+            Payload::Push(push) => push.branch(),
+            Payload::PullRequest(pr) => Some(&pr.pull_request.base.ref_),
+            Payload::PullRequestReview(review) => Some(&review.pull_request.base.ref_),
+            Payload::PullRequestReviewComment(comment) => Some(&comment.pull_request.base.ref_),
+            Payload::Create(create) if create.ref_type == RefType::Branch => Some(&create.ref_),
+            Payload::Delete(delete) if delete.ref_type == RefType::Branch => Some(&delete.ref_),
+            Payload::WorkflowRun(run) => run.workflow_run.head_branch.as_deref(),
+            Payload::CheckSuite(suite) => suite.check_suite.head_branch.as_deref(),
+            _ => None,
+        }
+    }
 
-            // multiple display_name
-            let mut display_name = r.display_name.clone();
-            if let Some(res) = v.get(&r.channel) {
-                display_name = res.display_name.to_string() + "&" + &display_name;
-            }
+    // workflow 名. check_suite の場合は GitHub App 名
+    pub fn workflow(&self) -> Option<&str> {
+        match &self {
+            Payload::WorkflowRun(run) => Some(&run.workflow.name),
+            Payload::CheckSuite(suite) => Some(&suite.check_suite.app.name),
+            _ => None,
+        }
+    }
 
-            let res = RuleMatchResult {
-                display_name,
-                channel: r.channel.clone(),
-            };
-            v.insert(r.channel.clone(), res);
+    pub fn conclusion(&self) -> Option<&common::Conclusion> {
+        match &self {
+            Payload::WorkflowRun(run) => run.workflow_run.conclusion.as_ref(),
+            Payload::CheckSuite(suite) => suite.check_suite.conclusion.as_ref(),
+            _ => None,
         }
+    }
 
-        v
+    pub fn tag(&self) -> Option<&str> {
+        match &self {
+            Payload::Push(push) => push.tag(),
+            Payload::Release(release) => Some(&release.release.tag_name),
+            Payload::Create(create) if create.ref_type == RefType::Tag => Some(&create.ref_),
+            Payload::Delete(delete) if delete.ref_type == RefType::Tag => Some(&delete.ref_),
+            _ => None,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::github::*;
+    use crate::test_util::try_de as de;
+
+    #[test]
+    fn de_issue_comment() {
+        assert!(matches!(
+            de("issue_comment", "issue_comment_created.json"),
+            Some(Payload::IssueComment(_))
+        ));
+    }
+
+    #[test]
+    fn de_issue() {
+        assert!(matches!(
+            de("issues", "issues_opened.json"),
+            Some(Payload::Issues(_))
+        ));
+        assert!(matches!(
+            de("issues", "issues_assigned.json"),
+            Some(Payload::Issues(_))
+        ));
+    }
+
+    #[test]
+    fn de_pull_request() {
+        assert!(matches!(
+            de("pull_request", "pull_request_opened.json"),
+            Some(Payload::PullRequest(_))
+        ));
+    }
+
+    #[test]
+    fn pull_request_action() {
+        assert!(matches!(
+            serde_json::from_str("\"review_requested\"").unwrap(),
+            PullRequestAction::ReviewRequested
+        ));
+        assert!(matches!(
+            serde_json::from_str("\"auto_merge_enabled\"").unwrap(),
+            PullRequestAction::AutoMergeEnabled
+        ));
+    }
+
+    // 知らない action でも deserialize に失敗させない
+    #[test]
+    fn unknown_action() {
+        let Some(Payload::PullRequest(pr)) = de("pull_request", "pull_request_enqueued.json")
+        else {
+            panic!("pull_request");
+        };
+        assert_eq!(pr.action, PullRequestAction::Unknown);
+        let Some(Payload::Issues(issues)) = de("issues", "issues_typed.json") else {
+            panic!("issues");
+        };
+        assert_eq!(issues.action, IssuesAction::Unknown);
+        assert_eq!(
+            serde_json::from_str::<DiscussionAction>("\"new_action\"").unwrap(),
+            DiscussionAction::Unknown
+        );
+    }
 
-    fn de(test_json: &str) -> Payload {
-        let path = format!("test/{}", test_json);
-        let payload = std::fs::read_to_string(path).unwrap();
-        let p: Payload = serde_json::from_str(&payload).unwrap();
-        p
-    }
-
-    // TODO: add test for OSS
-
-    //#[test]
-    //fn de_issue_comment() {
-    //    assert!(matches!(de("issue_comment.json"), Payload::IssueComment(_)));
-    //}
-
-    //#[test]
-    //fn de_issue() {
-    //    assert!(matches!(de("issue_open.json"), Payload::Issues(_)));
-    //    assert!(matches!(de("issue_assigned.json"), Payload::Issues(_)));
-    //    assert!(matches!(de("issue_labeled.json"), Payload::Issues(_)));
-    //}
-
-    //#[test]
-    //fn de_pull_request() {
-    //    assert!(matches!(
-    //        de("pull_request_assign.json"),
-    //        Payload::PullRequest(_)
-    //    ));
-    //}
-
-    //#[test]
-    //fn issues_action() {
-    //    assert!(matches!(
-    //        serde_json::from_str("\"opened\"").unwrap(),
-    //        IssuesAction::Opened
-    //    ));
-    //    assert!(matches!(
-    //        serde_json::from_str("\"closed\"").unwrap(),
-    //        IssuesAction::Closed
-    //    ));
-    //}
+    #[test]
+    fn de_pull_request_review() {
+        let p = de("pull_request_review", "pull_request_review_approved.json");
+        assert!(matches!(&p, Some(Payload::PullRequestReview(_))));
+        assert_eq!(
+            p.unwrap().review_state(),
+            Some(&common::ReviewState::Approved)
+        );
+
+        let p = de(
+            "pull_request_review",
+            "pull_request_review_changes_requested.json",
+        );
+        assert_eq!(
+            p.unwrap().review_state(),
+            Some(&common::ReviewState::ChangesRequested)
+        );
+    }
+
+    #[test]
+    fn de_pull_request_review_comment() {
+        assert!(matches!(
+            de(
+                "pull_request_review_comment",
+                "pull_request_review_comment_created.json"
+            ),
+            Some(Payload::PullRequestReviewComment(_))
+        ));
+    }
+
+    #[test]
+    fn de_push() {
+        let p = de("push", "push.json").unwrap();
+        assert!(matches!(&p, Payload::Push(_)));
+        assert_eq!(p.branch(), Some("main"));
+
+        let p = de("push", "push_tag.json").unwrap();
+        assert_eq!(p.branch(), None);
+    }
+
+    #[test]
+    fn de_release() {
+        let p = de("release", "release_published.json").unwrap();
+        assert!(matches!(&p, Payload::Release(_)));
+        assert_eq!(p.tag(), Some("v1.0.0"));
+
+        let p = de("create", "create_tag.json").unwrap();
+        assert_eq!(p.tag(), Some("v1.0.0"));
+        assert_eq!(p.branch(), None);
+
+        let p = de("delete", "delete_branch.json").unwrap();
+        assert_eq!(p.tag(), None);
+        assert_eq!(p.branch(), Some("feature/review"));
+    }
+
+    #[test]
+    fn de_workflow_run() {
+        let p = de("workflow_run", "workflow_run_failure.json").unwrap();
+        assert!(matches!(&p, Payload::WorkflowRun(_)));
+        assert_eq!(p.workflow(), Some("Rust"));
+        assert_eq!(p.conclusion(), Some(&common::Conclusion::Failure));
+
+        let p = de("check_suite", "check_suite_timed_out.json").unwrap();
+        assert!(matches!(&p, Payload::CheckSuite(_)));
+        assert_eq!(p.workflow(), Some("Buildkite"));
+        assert_eq!(p.conclusion(), Some(&common::Conclusion::TimedOut));
+    }
+
+    #[test]
+    fn de_discussion() {
+        let p = de("discussion", "discussion_created.json").unwrap();
+        assert!(matches!(&p, Payload::Discussion(_)));
+        assert_eq!(p.title(), "RFC: thread follow-up events");
+        assert_eq!(p.labels()[0].name, "rfc");
+
+        let p = de("discussion_comment", "discussion_comment_created.json").unwrap();
+        assert!(matches!(&p, Payload::DiscussionComment(_)));
+        assert_eq!(p.body(), "Sounds good to me.");
+    }
+
+    #[test]
+    fn de_unknown_event() {
+        assert!(de("ping", "ping.json").is_none());
+        assert!(de("star", "ping.json").is_none());
+    }
+
+    #[test]
+    fn de_event_mismatch() {
+        // issues の payload を issue_comment として送られたら失敗させる
+        let payload = std::fs::read_to_string("test/issues_opened.json").unwrap();
+        assert!(Payload::from_event("issue_comment", payload.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn issues_action() {
+        assert!(matches!(
+            serde_json::from_str("\"opened\"").unwrap(),
+            IssuesAction::Opened
+        ));
+        assert!(matches!(
+            serde_json::from_str("\"closed\"").unwrap(),
+            IssuesAction::Closed
+        ));
+    }
 }
@@ -3,82 +3,252 @@ use std::sync::Arc;
 
 use structopt::StructOpt;
 
-use serde::Deserialize;
-
-use regex::RegexBuilder;
-
-use actix_web::error::ErrorBadRequest;
-use actix_web::{web, App, Error, FromRequest, HttpRequest, HttpResponse, HttpServer, Result};
+use actix_web::http::StatusCode;
+use actix_web::{
+    web, App, Error, FromRequest, HttpRequest, HttpResponse, HttpServer, ResponseError, Result,
+};
 
 use futures::future::{Future, FutureExt};
-use futures::stream::{StreamExt, TryStreamExt};
+use futures::stream::TryStreamExt;
 
 use tracing::{debug, error, info, warn};
 
-use crypto_hashes::sha2::Sha256;
-use hmac::{Hmac, Mac};
-
-type HmacSha256 = Hmac<Sha256>;
-
+mod check;
+mod config;
+mod delivery;
 mod github;
 mod message;
+mod metrics;
+mod queue;
+mod rule;
+mod signature;
+mod simulate;
 mod slack;
+#[cfg(test)]
+mod test_util;
+mod thread;
+mod util;
+
+use config::{Config, SharedConfig};
+use delivery::{Claim, Deliveries};
+use metrics::Metrics;
+use queue::{Job, Queue};
+use signature::{Signature, SignaturePolicy};
+use thread::{Thread, Threads};
+
+// 起動時に 1 つ作るだけなので Opt を Box にはしない
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, StructOpt)]
+#[structopt(name = "hubhook")]
+enum Command {
+    #[structopt(about = "Run the webhook server (default)")]
+    Serve(Opt),
+    #[structopt(about = "Validate a config file and report suspicious rules")]
+    Check {
+        #[structopt(parse(from_os_str))]
+        config_path: std::path::PathBuf,
+    },
+    #[structopt(
+        about = "Show which rules match a saved webhook payload, without posting to Slack"
+    )]
+    Simulate {
+        #[structopt(parse(from_os_str))]
+        config_path: std::path::PathBuf,
+        // X-GitHub-Event の値 (e.g. pull_request)
+        event: String,
+        #[structopt(parse(from_os_str))]
+        payload_path: std::path::PathBuf,
+        // serve の THREAD_DB を渡すと thread への返信や書き換えになるかも表示する
+        #[structopt(long, parse(from_os_str))]
+        thread_db: Option<std::path::PathBuf>,
+    },
+}
+
+impl Command {
+    // 既存の deploy (引数なしや option だけ) のため, subcommand が無ければ serve とみなす
+    fn from_args_or_serve() -> Self {
+        const SUBCOMMANDS: &[&str] = &[
+            "serve",
+            "check",
+            "simulate",
+            "help",
+            "-h",
+            "--help",
+            "-V",
+            "--version",
+        ];
+
+        let mut args: Vec<_> = std::env::args_os().collect();
+        let first = args.get(1).and_then(|a| a.to_str());
+        if !first.map_or(false, |a| SUBCOMMANDS.contains(&a)) {
+            args.insert(1, "serve".into());
+        }
+        Command::from_iter(args)
+    }
+}
 
 #[derive(Debug, Clone, StructOpt)]
-#[structopt(name = "hubhook")]
 struct Opt {
     #[structopt(env, default_value = "/config/config.json", long, short)]
     config_path: String,
 
+    #[structopt(long, env, default_value = "0.0.0.0")]
+    hubhook_host: String,
     #[structopt(long, env)]
-    hubhook_port: usize,
+    hubhook_port: u16,
     #[structopt(long, env)]
     slack_token: String,
-    #[structopt(long, env)]
-    webhook_secret: String,
+    // mock server や proxy を使う場合に変える
+    #[structopt(long, env, default_value = "https://slack.com/api/")]
+    slack_api_url: String,
+    #[structopt(long, env, default_value = "10")]
+    slack_timeout_secs: u64,
+    #[structopt(long, env, default_value = "5")]
+    slack_connect_timeout_secs: u64,
+    // rotation 用に複数指定できる. --webhook-secret を繰り返すか, WEBHOOK_SECRET に 1 行 1 つで書く
+    // secret は "," なども含みうるので改行でだけ区切る
+    #[structopt(
+        long,
+        env,
+        required = true,
+        number_of_values = 1,
+        use_delimiter = true,
+        value_delimiter = "\n"
+    )]
+    webhook_secret: Vec<String>,
+    // sha256, sha256-or-sha1, sha1
+    #[structopt(long, env, default_value = "sha256")]
+    signature_policy: SignaturePolicy,
 
     #[structopt(long, env)]
     sentry_dsn: String,
 
     #[structopt(long)]
     debug: bool,
+
+    // 署名を検証しない. localhost で動かす時のみ使える
+    #[structopt(long)]
+    insecure_skip_signature: bool,
+
+    // 処理済みの X-GitHub-Delivery を覚えておく期間と件数
+    #[structopt(long, env, default_value = "86400")]
+    delivery_ttl_secs: u64,
+    #[structopt(long, env, default_value = "10000")]
+    delivery_capacity: usize,
+    // 指定すると SQLite に保存し, 再起動後も再送を無視する
+    #[structopt(long, env, parse(from_os_str))]
+    delivery_db: Option<std::path::PathBuf>,
+    // 処理済みの delivery も処理する (debug 用)
+    #[structopt(long)]
+    allow_redelivery: bool,
+
+    // 同じ issue / PR の通知を thread にまとめるため, 最初の message の ts を保存する
+    // 指定しなければ memory に置くので, 再起動すると新しい thread になる
+    #[structopt(long, env, parse(from_os_str))]
+    thread_db: Option<std::path::PathBuf>,
+    // 親を覚えておく期間と件数. 過ぎた issue / PR の event は新しく投稿する
+    #[structopt(long, env, default_value = "7776000")]
+    thread_ttl_secs: u64,
+    #[structopt(long, env, default_value = "100000")]
+    thread_capacity: usize,
+
+    // Slack への投稿は queue に積んで background で行う
+    #[structopt(long, env, default_value = "1000")]
+    queue_capacity: usize,
+    #[structopt(long, env, default_value = "4")]
+    slack_concurrency: usize,
+    // 終了時に queue に残った投稿を待つ時間
+    #[structopt(long, env, default_value = "30")]
+    queue_drain_timeout_secs: u64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct Config {
-    pub rule: Vec<Rule>,
+impl Opt {
+    // 末尾の改行などでできた空の secret は誰でも署名できてしまうので使わない
+    fn webhook_secrets(&self) -> Vec<&str> {
+        self.webhook_secret
+            .iter()
+            .map(|s| s.trim_end_matches('\r'))
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    // bind と同じように解決し, 全ての address が loopback なら localhost とみなす
+    fn is_localhost(&self) -> bool {
+        use std::net::ToSocketAddrs;
+
+        match (self.hubhook_host.as_str(), self.hubhook_port).to_socket_addrs() {
+            Ok(addrs) => {
+                let addrs: Vec<_> = addrs.collect();
+                !addrs.is_empty() && addrs.iter().all(|a| a.ip().is_loopback())
+            }
+            Err(_) => false,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub struct RuleMatchResult {
-    display_name: String,
-    channel: String,
+#[derive(Debug)]
+struct Data {
+    event: String,            // X-GitHub-Event
+    delivery: Option<String>, // X-GitHub-Delivery
+    payload: Option<github::Payload>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct Rule {
-    pub channel: String,
-    pub query: Query,
-    pub exclude_query: Option<Query>,
-    pub display_name: String,
+// webhook を受け付けなかった理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    UserAgentMismatch,
+    MissingEvent,
+    MissingSignature,
+    MalformedSignature,
+    SignatureMismatch,
+    InvalidPayload,
 }
 
-// TODO: empty check
-#[derive(Debug, Clone, Deserialize)]
-pub struct Query {
-    repo: Option<String>,
-    topic: Option<String>,
-    user: Option<String>,
-    //event: Option<String>,
-    title: Option<String>,
-    body: Option<String>,
-    label: Option<String>,
-    //review_state: Option<String>,
+impl Rejection {
+    // metrics の label
+    fn reason(&self) -> &'static str {
+        match self {
+            Rejection::UserAgentMismatch => "user_agent_mismatch",
+            Rejection::MissingEvent => "missing_event",
+            Rejection::MissingSignature => "missing_signature",
+            Rejection::MalformedSignature => "malformed_signature",
+            Rejection::SignatureMismatch => "signature_mismatch",
+            Rejection::InvalidPayload => "invalid_payload",
+        }
+    }
 }
 
-#[derive(Debug)]
-struct Data {
-    json: web::Json<github::Payload>,
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Rejection::UserAgentMismatch => "user-agent mismatch",
+            Rejection::MissingEvent => "missing X-GitHub-Event header",
+            Rejection::MissingSignature => "missing signature header",
+            Rejection::MalformedSignature => "malformed signature header",
+            Rejection::SignatureMismatch => "signature mismatch",
+            Rejection::InvalidPayload => "invalid payload",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl ResponseError for Rejection {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Rejection::MissingSignature | Rejection::SignatureMismatch => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+fn reject(req: &HttpRequest, rejection: Rejection) -> Error {
+    let count = req
+        .app_data::<web::Data<Metrics>>()
+        .map(|m| m.inc("hubhook_webhook_rejections_total", rejection.reason()))
+        .unwrap_or_default();
+    warn!("webhook rejected: {rejection} (count = {count})");
+
+    rejection.into()
 }
 
 impl FromRequest for Data {
@@ -91,57 +261,74 @@ impl FromRequest for Data {
 
         debug!("{:?}", req);
 
+        let opt = req.app_data::<web::Data<Arc<Opt>>>().unwrap().clone();
+
         let headers = req.headers();
-        let ua = headers.get("user-agent").unwrap();
-        let ua_str = ua.to_str().unwrap();
-        if !ua_str.starts_with("GitHub-Hookshot") {
-            error!("user-agent mismatch");
-            return Box::pin(err(ErrorBadRequest("user-agent mismatch")));
+        let ua = headers
+            .get("user-agent")
+            .and_then(|ua| ua.to_str().ok())
+            .unwrap_or_default();
+        if !ua.starts_with("GitHub-Hookshot") {
+            return Box::pin(err(reject(req, Rejection::UserAgentMismatch)));
         }
 
-        use actix_web::error::PayloadError;
-        use actix_web::web::Bytes;
-        let sig256: Vec<u8> = {
-            let sig = headers.get("x-hub-signature-256").unwrap();
-            let sig = String::from_utf8(sig.as_bytes().to_vec()).unwrap();
-            let sig = sig.strip_prefix("sha256=").unwrap();
-            hex::decode(sig).unwrap()
+        let event = match headers.get("x-github-event").map(|e| e.to_str()) {
+            Some(Ok(event)) => event.to_string(),
+            _ => return Box::pin(err(reject(req, Rejection::MissingEvent))),
+        };
+        let delivery = headers
+            .get("x-github-delivery")
+            .and_then(|d| d.to_str().ok())
+            .map(|d| d.to_string());
+
+        let signature = match Signature::from_headers(headers, opt.signature_policy) {
+            Ok(signature) => Some(signature),
+            Err(rejection) if opt.insecure_skip_signature => {
+                warn!("{rejection} (ignored by --insecure-skip-signature)");
+                None
+            }
+            Err(rejection) => return Box::pin(err(reject(req, rejection))),
         };
 
         let req = req.clone();
         let pd = payload.take();
         async move {
-            let opt = req.app_data::<web::Data<Arc<Opt>>>().unwrap();
-            let p = pd
+            let p: Vec<u8> = pd
                 .try_fold(Vec::new(), |mut acc, chunk| async move {
                     acc.extend(chunk);
                     Ok(acc)
                 })
-                .await;
-            let p: Vec<u8> = p.unwrap();
-
-            let webhook_secret = &opt.webhook_secret.as_bytes();
-            let mut mac = HmacSha256::new_from_slice(webhook_secret).unwrap();
-            mac.update(&p);
-            let result = mac.finalize();
+                .await
+                .map_err(|e| {
+                    error!("could not read payload: {e}");
+                    reject(&req, Rejection::InvalidPayload)
+                })?;
 
             // validate signature
-            if !compare_slice(&sig256, &result.into_bytes()) {
-                error!("signature mismatch");
-                if !opt.debug {
-                    return Err(ErrorBadRequest("signature mismatch!"));
+            match signature.and_then(|s| s.verify(&opt.webhook_secrets(), &p)) {
+                // どの secret が使われているか見て, 古い secret を外せるか判断する
+                Some(i) => {
+                    info!("signature matched webhook secret #{i}");
+                    if let Some(metrics) = req.app_data::<web::Data<Metrics>>() {
+                        metrics.inc("hubhook_webhook_secret_matches_total", &i.to_string());
+                    }
+                }
+                None if opt.insecure_skip_signature => {
+                    warn!("signature mismatch (ignored by --insecure-skip-signature)")
                 }
+                None => return Err(reject(&req, Rejection::SignatureMismatch)),
             }
 
-            let b = Bytes::from(p);
-            let st = futures::stream::once(async { Ok::<_, PayloadError>(b) });
-            let mut p = actix_web::dev::Payload::Stream {
-                payload: st.boxed_local(),
-            };
+            let payload = github::Payload::from_event(&event, &p).map_err(|e| {
+                error!("could not deserialize {event} payload: {e}");
+                reject(&req, Rejection::InvalidPayload)
+            })?;
 
-            let json = web::Json::<github::Payload>::from_request(&req, &mut p).await?;
-
-            Ok(Data { json }) // validate success
+            Ok(Data {
+                event,
+                delivery,
+                payload,
+            }) // validate success
         }
         .boxed_local()
     }
@@ -149,8 +336,29 @@ impl FromRequest for Data {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let opt = Opt::from_args();
+    match Command::from_args_or_serve() {
+        Command::Serve(opt) => serve(opt).await,
+        Command::Check { config_path } => {
+            if !check::run(&config_path) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Simulate {
+            config_path,
+            event,
+            payload_path,
+            thread_db,
+        } => {
+            if !simulate::run(&config_path, &event, &payload_path, thread_db.as_deref()) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
 
+async fn serve(opt: Opt) -> std::io::Result<()> {
     let _guard = sentry::init((
         opt.sentry_dsn.clone(),
         sentry::ClientOptions {
@@ -161,6 +369,7 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_BACKTRACE", "1");
 
     let port = opt.hubhook_port;
+    let host = opt.hubhook_host.clone();
 
     let level = if opt.debug {
         tracing::Level::DEBUG
@@ -169,169 +378,535 @@ async fn main() -> std::io::Result<()> {
     };
     tracing_subscriber::fmt().with_max_level(level).init();
 
-    let cfg: Config = {
-        use std::io::Read;
-
-        info!("loading config file from \"{}\"", opt.config_path);
-        let f = std::fs::File::open(opt.config_path.clone());
-        if let Err(ref _f) = f {
-            error!("could not open config file!");
+    if opt.insecure_skip_signature {
+        if !opt.is_localhost() {
+            let msg = format!(
+                "--insecure-skip-signature can only be used with localhost, but hubhook_host = {}",
+                opt.hubhook_host
+            );
+            error!("{msg}");
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
         }
 
-        let mut f = f.unwrap();
-        let mut config = String::new();
+        let msg = "webhook signature verification is DISABLED (--insecure-skip-signature). never use this in production!";
+        error!("{msg}");
+        sentry::capture_message(msg, sentry::Level::Warning);
+    }
 
-        if f.read_to_string(&mut config).is_err() {
-            error!("could not read config file");
+    info!("loading config file from \"{}\"", opt.config_path);
+    let cfg = match Config::load(opt.config_path.as_ref()) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("could not load config file: {e}");
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                e.to_string(),
+            ));
         }
-
-        let res = serde_json::from_str(&config);
-        if let Err(ref e) = res {
-            error!("could not deserialize config file!");
-            error!("{}", e);
+    };
+    let cfg = Arc::new(SharedConfig::from_pointee(cfg));
+    config::spawn_reloader(opt.config_path.clone().into(), cfg.clone());
+
+    // worker 間で共有する
+    let metrics = web::Data::new(Metrics::default());
+
+    let ttl = std::time::Duration::from_secs(opt.delivery_ttl_secs);
+    let deliveries = match &opt.delivery_db {
+        Some(path) => {
+            info!("recording deliveries in \"{}\"", path.display());
+            Deliveries::sqlite(path, ttl, opt.delivery_capacity).map_err(|e| {
+                error!("could not open delivery db: {e}");
+                std::io::Error::new(std::io::ErrorKind::Other, e)
+            })?
         }
-        res.unwrap()
+        None => Deliveries::memory(ttl, opt.delivery_capacity),
     };
+    let deliveries = web::Data::new(deliveries);
+
+    let ttl = std::time::Duration::from_secs(opt.thread_ttl_secs);
+    let threads = match &opt.thread_db {
+        Some(path) => {
+            info!("recording threads in \"{}\"", path.display());
+            Threads::sqlite(path, ttl, opt.thread_capacity).map_err(|e| {
+                error!("could not open thread db: {e}");
+                std::io::Error::new(std::io::ErrorKind::Other, e)
+            })?
+        }
+        None => Threads::memory(ttl, opt.thread_capacity),
+    };
+
+    let slack = slack::Client::new(
+        opt.slack_token.clone(),
+        &opt.slack_api_url,
+        std::time::Duration::from_secs(opt.slack_timeout_secs),
+        std::time::Duration::from_secs(opt.slack_connect_timeout_secs),
+    )
+    .map_err(|e| {
+        error!("{e}");
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    })?;
+    let (queue, worker) = Queue::start(
+        slack,
+        Arc::new(threads),
+        opt.queue_capacity,
+        opt.slack_concurrency,
+        metrics.clone().into_inner(),
+    );
+    let drain_timeout = std::time::Duration::from_secs(opt.queue_drain_timeout_secs);
+    if opt.allow_redelivery {
+        warn!("--allow-redelivery: already processed deliveries will be posted again");
+    }
 
     HttpServer::new(move || {
         App::new()
             .wrap(sentry_actix::Sentry::new())
-            .app_data(web::Data::new(Arc::new(cfg.clone()))) // memo: https://github.com/actix/actix-web/issues/1454#issuecomment-867897725
+            .app_data(web::Data::from(cfg.clone()))
             .app_data(web::Data::new(Arc::new(opt.clone())))
+            .app_data(metrics.clone())
+            .app_data(deliveries.clone())
+            .app_data(web::Data::new(queue.clone()))
             .service(web::resource("/webhook").route(web::post().to(webhook)))
+            .service(web::resource("/metrics").route(web::get().to(metrics_handler)))
             .service(web::resource("/healthcheck").route(web::get().to(HttpResponse::Ok)))
     })
-    .bind(format!("0.0.0.0:{}", port))?
+    .bind((host.as_str(), port))?
     .run()
-    .await
+    .await?;
+
+    // server が止まり queue が drop されたので, 残っている投稿を待つ
+    info!("draining the Slack queue");
+    if actix_web::rt::time::timeout(drain_timeout, worker)
+        .await
+        .is_err()
+    {
+        error!("the Slack queue was not drained in {drain_timeout:?}, some messages are lost");
+    }
+    Ok(())
 }
 
 async fn webhook(
     opt: web::Data<Arc<Opt>>,
-    cfg: web::Data<Arc<Config>>,
-    data: Option<Data>,
+    cfg: web::Data<SharedConfig>,
+    metrics: web::Data<Metrics>,
+    deliveries: web::Data<Deliveries>,
+    queue: web::Data<Queue>,
+    data: Data,
 ) -> Result<HttpResponse> {
-    let payload = if let Some(data) = data {
-        data.json
+    let event = &data.event;
+    let delivery = data.delivery.as_deref().unwrap_or("-");
+    let payload = if let Some(payload) = data.payload {
+        payload
     } else {
-        return Ok(HttpResponse::BadRequest().body("bad request"));
+        // ping など hubhook が扱わない event は受け取るだけにする
+        if event == "ping" {
+            info!("ping received. delivery = {delivery}");
+        } else {
+            warn!("unsupported event: {event}. delivery = {delivery}");
+        }
+        return Ok(HttpResponse::Ok().body("webhook"));
     };
 
-    let payload = payload.into_inner();
+    // GitHub の retry や手動の Redeliver で二重に投稿しない
+    // 失敗した場合は guard を drop して再送を受け付ける
+    let deliveries = deliveries.into_inner();
+    let guard = match data.delivery.as_deref() {
+        Some(id) if !opt.allow_redelivery => match deliveries.claim(id) {
+            Claim::New(guard) => Some(guard),
+            Claim::Duplicate => {
+                info!("skip already processed delivery: {id}. event = {event}");
+                metrics.inc("hubhook_webhook_skipped_total", "duplicate_delivery");
+                return Ok(HttpResponse::Ok().body("duplicate delivery"));
+            }
+        },
+        _ => None,
+    };
 
-    //post_test(&opt, &payload).await;
+    let mut job = match render(&cfg, event, delivery, payload) {
+        Ok(Some(job)) => job,
+        Ok(None) => {
+            if let Some(guard) = guard {
+                guard.done();
+            }
+            return Ok(HttpResponse::Ok().body("webhook"));
+        }
+        Err(()) => return Ok(HttpResponse::Ok().body("webhook")),
+    };
 
+    // Slack の応答を待たずに返す. 処理済みにするのは投稿できてから
+    job.claim = guard;
+    if let Err(job) = queue.push(job) {
+        warn!(
+            "queue is full ({} jobs), rejecting {event}. delivery = {}",
+            queue.len(),
+            job.delivery
+        );
+        metrics.inc("hubhook_webhook_skipped_total", "queue_full");
+        return Ok(HttpResponse::ServiceUnavailable().body("queue is full"));
+    }
+
+    Ok(HttpResponse::Accepted().body("queued"))
+}
+
+// 投稿するものが無ければ Ok(None)
+fn render(
+    cfg: &SharedConfig,
+    event: &str,
+    delivery: &str,
+    payload: github::Payload,
+) -> Result<Option<Job>, ()> {
     // match rule
-    let matches = payload.match_rules(&cfg.rule);
+    let cfg = cfg.load();
+    let matches = cfg.rules.matches(&payload);
+    if matches.is_empty() {
+        return Ok(None);
+    }
 
-    for (channel, m) in matches {
-        let msg: Result<slack::Message, _> = (&payload).try_into();
-        if let Ok(msg) = msg {
-            msg.post_message(&opt.slack_token, &channel, Some(&m.display_name))
-                .await;
-        } else {
+    // 状態が変わった issue / PR は最初の message を書き換える
+    let thread = Thread::of(&payload);
+    let opening = match thread {
+        Some(Thread::Update { .. }) => message::opening(&payload),
+        _ => None,
+    };
+
+    let msg: Result<slack::Message, _> = (&payload).try_into();
+    let msg = match msg {
+        Ok(msg) => Some(msg),
+        Err(message::Error::Unsupported(what)) if opening.is_some() => {
+            debug!("{event} ({what}) only updates the opening message. delivery = {delivery}");
+            None
+        }
+        Err(message::Error::Unsupported(what)) => {
+            debug!("skip {event} ({what}). delivery = {delivery}");
+            return Ok(None);
+        }
+        Err(e) => {
             error!(
-                "GitHub payload -> slack::Message failed. link = {}",
+                "GitHub payload -> slack::Message failed: {e}. event = {event}, delivery = {delivery}, link = {}",
                 &payload.url()
             );
+            sentry::with_scope(
+                |scope| {
+                    scope.set_tag("event", event);
+                    scope.set_tag("delivery", delivery);
+                    scope.set_extra("link", payload.url().as_str().into());
+                },
+                || sentry::capture_error(&e),
+            );
             //error!("payload: {:#?}", &payload);
+            return Err(());
         }
-    }
+    };
 
-    Ok(HttpResponse::Ok().body("webhook"))
+    Ok(Some(Job {
+        message: msg,
+        targets: matches
+            .into_values()
+            .map(|m| (m.channel, m.display_name))
+            .collect(),
+        event: event.to_string(),
+        delivery: delivery.to_string(),
+        thread,
+        opening,
+        claim: None,
+    }))
 }
 
-impl Rule {
-    fn check_match(&self, payload: &github::Payload) -> bool {
-        let include_query_result = Rule::match_results(&self.query, payload).iter().all(|&r| r);
+async fn metrics_handler(metrics: web::Data<Metrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render())
+}
 
-        if let Some(exclude_query) = &self.exclude_query {
-            let exclude_query_result = Rule::match_results(exclude_query, payload)
-                .iter()
-                .any(|&r| r);
-            include_query_result && !exclude_query_result
-        } else {
-            include_query_result
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use crypto_hashes::sha2::Sha256;
+    use hmac::{Hmac, Mac};
+
+    const SECRET: &str = "It's a Secret to Everybody";
+
+    fn opt(args: &[&str]) -> Opt {
+        let mut argv = vec![
+            "hubhook",
+            "--hubhook-port",
+            "8080",
+            "--slack-token",
+            "xoxb-",
+            "--webhook-secret",
+            "new-secret",
+            "--webhook-secret",
+            SECRET,
+            "--sentry-dsn",
+            "",
+        ];
+        argv.extend(args);
+        Opt::from_iter(argv)
     }
 
-    fn match_results(query: &Query, payload: &github::Payload) -> Vec<bool> {
-        let r_repo = Rule::match_query(query.repo.as_ref(), &payload.repo().full_name);
-
-        let topics = &payload.repo().topics;
-        let topics = topics.iter().collect();
-        let r_topic = Rule::match_query_vec(query.topic.as_ref(), topics);
-
-        let r_sender = Rule::match_query(query.user.as_ref(), &payload.sender().login);
-        let r_title = Rule::match_query(query.title.as_ref(), payload.title());
-        let r_body = Rule::match_query(query.body.as_ref(), payload.body());
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
 
-        let labels = payload.labels().iter().collect();
-        let r_labels = Rule::match_query_vec(query.label.as_ref(), labels);
+    fn deliveries() -> web::Data<Deliveries> {
+        web::Data::new(Deliveries::memory(std::time::Duration::from_secs(60), 100))
+    }
 
-        vec![r_repo, r_topic, r_sender, r_title, r_body, r_labels]
-            .into_iter()
-            .flatten()
-            .collect()
+    #[test]
+    fn webhook_secrets() {
+        assert_eq!(opt(&[]).webhook_secrets(), ["new-secret", SECRET]);
+
+        // "," を含む secret も 1 つの secret として扱う
+        let opt = Opt::from_iter([
+            "hubhook",
+            "--hubhook-port",
+            "8080",
+            "--slack-token",
+            "xoxb-",
+            "--webhook-secret",
+            "a,b",
+            "--sentry-dsn",
+            "",
+        ]);
+        assert_eq!(opt.webhook_secrets(), ["a,b"]);
+
+        // 環境変数では 1 行に 1 つ. 空行は無視する
+        std::env::set_var("WEBHOOK_SECRET", "new,1\r\nold,2\n\n");
+        let opt = Opt::from_iter([
+            "hubhook",
+            "--hubhook-port",
+            "8080",
+            "--slack-token",
+            "xoxb-",
+            "--sentry-dsn",
+            "",
+        ]);
+        std::env::remove_var("WEBHOOK_SECRET");
+        assert_eq!(opt.webhook_secrets(), ["new,1", "old,2"]);
     }
 
-    fn match_query(query: Option<&String>, payload: &str) -> Option<bool> {
-        query?;
-        Some(Rule::match_query_impl(query.unwrap(), payload))
+    #[test]
+    fn localhost() {
+        assert!(!opt(&[]).is_localhost());
+        for host in ["localhost", "127.0.0.1", "127.0.0.2", "::1"] {
+            assert!(opt(&["--hubhook-host", host]).is_localhost(), "{host}");
+        }
+        // "[::1]" は bind できない
+        for host in ["0.0.0.0", "::", "192.168.0.1", "[::1]", "example.invalid"] {
+            assert!(!opt(&["--hubhook-host", host]).is_localhost(), "{host}");
+        }
     }
 
-    fn match_query_vec<T>(query: Option<&String>, payload: Vec<T>) -> Option<bool>
-    where
-        T: ToString, // Into<&str>にしようとしたけどダメだった
-    {
-        query?;
-        let query = query.unwrap();
+    #[actix_web::test]
+    async fn webhook_rejection() {
+        let metrics = web::Data::new(Metrics::default());
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(
+                    SharedConfig::from_pointee(Config::default()),
+                ))
+                .app_data(web::Data::new(Arc::new(opt(&[]))))
+                .app_data(metrics.clone())
+                .app_data(deliveries())
+                .app_data(web::Data::new(Queue::new(10).0))
+                .service(web::resource("/webhook").route(web::post().to(webhook))),
+        )
+        .await;
 
-        for p in payload {
-            if Rule::match_query_impl(query, &p.to_string()) {
-                return Some(true);
+        let body = std::fs::read("test/ping.json").unwrap();
+        let req = |headers: &[(&'static str, &str)]| {
+            let mut req = TestRequest::post()
+                .uri("/webhook")
+                .set_payload(body.clone());
+            for h in headers {
+                req = req.insert_header(*h);
             }
+            req.to_request()
+        };
+        let ua = ("user-agent", "GitHub-Hookshot/044aadd");
+        let event = ("x-github-event", "ping");
+        let sig = sign(&body);
+        let bad_sig = sign(b"other");
+
+        let cases = [
+            (vec![event], StatusCode::BAD_REQUEST),
+            (vec![ua], StatusCode::BAD_REQUEST),
+            (vec![ua, event], StatusCode::UNAUTHORIZED),
+            (
+                vec![ua, event, ("x-hub-signature-256", "sha256=zz")],
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                vec![ua, event, ("x-hub-signature-256", &bad_sig)],
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                vec![ua, event, ("x-hub-signature-256", &sig)],
+                StatusCode::OK,
+            ),
+        ];
+        for (headers, status) in cases {
+            let res = call_service(&app, req(&headers)).await;
+            assert_eq!(res.status(), status, "{headers:?}");
         }
 
-        Some(false)
+        let rendered = metrics.render();
+        for reason in [
+            "user_agent_mismatch",
+            "missing_event",
+            "missing_signature",
+            "malformed_signature",
+            "signature_mismatch",
+        ] {
+            let line = format!("hubhook_webhook_rejections_total{{reason=\"{reason}\"}} 1");
+            assert!(rendered.contains(&line), "{rendered}");
+        }
+        // 2 番目の secret で署名した
+        assert!(
+            rendered.contains("hubhook_webhook_secret_matches_total{reason=\"1\"} 1"),
+            "{rendered}"
+        );
     }
 
-    fn match_query_impl(query: &str, payload: &str) -> bool {
-        if query.is_empty() {
-            warn!("query is empty");
-            return false;
-        }
+    #[actix_web::test]
+    async fn webhook_insecure_skip_signature() {
+        let opt = opt(&["--hubhook-host", "127.0.0.1", "--insecure-skip-signature"]);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(
+                    SharedConfig::from_pointee(Config::default()),
+                ))
+                .app_data(web::Data::new(Arc::new(opt)))
+                .app_data(web::Data::new(Metrics::default()))
+                .app_data(deliveries())
+                .app_data(web::Data::new(Queue::new(10).0))
+                .service(web::resource("/webhook").route(web::post().to(webhook))),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/webhook")
+            .insert_header(("user-agent", "GitHub-Hookshot/044aadd"))
+            .insert_header(("x-github-event", "ping"))
+            .set_payload(std::fs::read("test/ping.json").unwrap())
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
 
-        let re = RegexBuilder::new(query)
-            .case_insensitive(true)
-            .build()
-            .unwrap();
-        if re.is_match(payload) {
-            return true;
+    #[actix_web::test]
+    async fn webhook_redelivery() {
+        let metrics = web::Data::new(Metrics::default());
+        let deliveries = deliveries();
+        let app = |args: &[&str]| {
+            App::new()
+                .app_data(web::Data::new(
+                    SharedConfig::from_pointee(Config::default()),
+                ))
+                .app_data(web::Data::new(Arc::new(opt(args))))
+                .app_data(metrics.clone())
+                .app_data(deliveries.clone())
+                .app_data(web::Data::new(Queue::new(10).0))
+                .service(web::resource("/webhook").route(web::post().to(webhook)))
+        };
+
+        let body = std::fs::read("test/issues_labeled.json").unwrap();
+        let req = |delivery: &str| {
+            TestRequest::post()
+                .uri("/webhook")
+                .insert_header(("user-agent", "GitHub-Hookshot/044aadd"))
+                .insert_header(("x-github-event", "issues"))
+                .insert_header(("x-github-delivery", delivery.to_string()))
+                .insert_header(("x-hub-signature-256", sign(&body)))
+                .set_payload(body.clone())
+                .to_request()
+        };
+        let server = init_service(app(&[])).await;
+        for (delivery, expected) in [
+            ("d-1", "webhook"),
+            ("d-1", "duplicate delivery"),
+            ("d-2", "webhook"),
+        ] {
+            let res = call_service(&server, req(delivery)).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(read_body(res).await, expected, "{delivery}");
         }
 
-        false
+        let debug = init_service(app(&["--allow-redelivery"])).await;
+        let res = call_service(&debug, req("d-1")).await;
+        assert_eq!(read_body(res).await, "webhook");
+
+        assert!(metrics
+            .render()
+            .contains("hubhook_webhook_skipped_total{reason=\"duplicate_delivery\"} 1"));
     }
-}
 
-#[allow(dead_code)]
-async fn post_test(opt: &Opt, payload: &github::Payload) {
-    let msg: slack::Message = payload.try_into().unwrap();
-    msg.post_message(&opt.slack_token, "tmp_hubhook", None)
+    #[actix_web::test]
+    async fn webhook_queue() {
+        let path = std::env::temp_dir().join(format!("hubhook-{}-queue.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{ "rule": [{ "channel": "dev", "display_name": "hubhook", "query": { "repo": "hubhook" } }] }"#,
+        )
+        .unwrap();
+        let cfg = Config::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let metrics = web::Data::new(Metrics::default());
+        let (queue, mut rx) = Queue::new(1);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(SharedConfig::from_pointee(cfg)))
+                .app_data(web::Data::new(Arc::new(opt(&[]))))
+                .app_data(metrics.clone())
+                .app_data(deliveries())
+                .app_data(web::Data::new(queue))
+                .service(web::resource("/webhook").route(web::post().to(webhook))),
+        )
         .await;
-}
 
-fn compare_slice(a: &[u8], b: &[u8]) -> bool {
-    use std::cmp::Ordering;
+        let body = std::fs::read("test/issues_labeled.json").unwrap();
+        let req = |delivery: &str| {
+            TestRequest::post()
+                .uri("/webhook")
+                .insert_header(("user-agent", "GitHub-Hookshot/044aadd"))
+                .insert_header(("x-github-event", "issues"))
+                .insert_header(("x-github-delivery", delivery.to_string()))
+                .insert_header(("x-hub-signature-256", sign(&body)))
+                .set_payload(body.clone())
+                .to_request()
+        };
 
-    if a.len() != b.len() {
-        return false;
-    }
-    for (ai, bi) in a.iter().zip(b.iter()) {
-        match ai.cmp(bi) {
-            Ordering::Equal => continue,
-            _ => return false,
-        }
+        // Slack に投稿する前に返す
+        let res = call_service(&app, req("d-1")).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+        // 溢れたものは再送できるように記録しない
+        let res = call_service(&app, req("d-2")).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // queue にある間は処理中として再送を無視する
+        let res = call_service(&app, req("d-1")).await;
+        assert_eq!(read_body(res).await, "duplicate delivery");
+
+        let job = rx.try_next().unwrap().unwrap();
+        assert_eq!(job.delivery, "d-1");
+        assert_eq!(job.targets, [("dev".to_string(), "hubhook".to_string())]);
+        assert!(job.claim.is_some());
+        assert_eq!(
+            job.message.unwrap().text,
+            "[arkedge/hubhook] Label `bug` added to issue by sksat"
+        );
+
+        // 投稿できずに claim を捨てたら再送を受け付ける (queue はまだ空いていない)
+        drop(job.claim);
+        let res = call_service(&app, req("d-1")).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(metrics
+            .render()
+            .contains("hubhook_webhook_skipped_total{reason=\"queue_full\"} 2"));
     }
-    true
 }
@@ -1,10 +1,34 @@
 use crate::github;
 use crate::slack;
 
+use once_cell::sync::Lazy;
+use regex::Regex;
 use tracing::info;
 
+// GitHub payload -> slack::Message の変換エラー
+#[derive(Debug)]
+pub enum Error {
+    // 通知しない action など. 意図的に変換しないのでエラー扱いしない
+    Unsupported(String),
+    // payload に必要なデータが入っていない
+    MissingField(&'static str),
+    RenderFailure(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Unsupported(what) => write!(f, "unsupported: {what}"),
+            Error::MissingField(field) => write!(f, "missing field in payload: {field}"),
+            Error::RenderFailure(e) => write!(f, "could not render message: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl TryFrom<&github::Payload> for slack::Message {
-    type Error = ();
+    type Error = Error;
 
     fn try_from(payload: &github::Payload) -> Result<Self, Self::Error> {
         use github::Payload;
@@ -22,6 +46,46 @@ impl TryFrom<&github::Payload> for slack::Message {
                 let ic: &github::IssueComment = ic;
                 ic.try_into()
             }
+            Payload::PullRequestReview(review) => {
+                let r: &github::PullRequestReview = review;
+                r.try_into()
+            }
+            Payload::PullRequestReviewComment(rc) => {
+                let rc: &github::PullRequestReviewComment = rc;
+                rc.try_into()
+            }
+            Payload::Push(push) => {
+                let p: &github::Push = push;
+                p.try_into()
+            }
+            Payload::Release(release) => {
+                let r: &github::Release = release;
+                r.try_into()
+            }
+            Payload::Create(create) => {
+                let c: &github::Create = create;
+                c.try_into()
+            }
+            Payload::Delete(delete) => {
+                let d: &github::Delete = delete;
+                d.try_into()
+            }
+            Payload::WorkflowRun(run) => {
+                let r: &github::WorkflowRun = run;
+                r.try_into()
+            }
+            Payload::CheckSuite(suite) => {
+                let s: &github::CheckSuite = suite;
+                s.try_into()
+            }
+            Payload::Discussion(discussion) => {
+                let d: &github::Discussion = discussion;
+                d.try_into()
+            }
+            Payload::DiscussionComment(dc) => {
+                let dc: &github::DiscussionComment = dc;
+                dc.try_into()
+            }
         }
     }
 }
@@ -47,13 +111,252 @@ fn users2str(assignees: &[github::common::User], delimiter: &str, to_link: bool)
     )
 }
 
+fn labels2str(labels: &[github::common::Label]) -> Option<String> {
+    if labels.is_empty() {
+        return None;
+    }
+
+    Some(
+        labels
+            .iter()
+            .map(|l| format!("`{}`", slack::escape(&l.name)))
+            .collect::<Vec<String>>()
+            .join(" "),
+    )
+}
+
+static HEADING: Lazy<Regex> = Lazy::new(|| Regex::new(r"^#{1,6}\s+(.*)$").unwrap());
+static LIST: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\s*)[-*+]\s+").unwrap());
+static BOLD: Lazy<Regex> = Lazy::new(|| Regex::new(r"\*\*(.+?)\*\*|__(.+?)__").unwrap());
+static STRIKE: Lazy<Regex> = Lazy::new(|| Regex::new(r"~~(.+?)~~").unwrap());
+static LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"!?\[([^\]]*)\]\(([^)\s]+)\)").unwrap());
+
+// GitHub Flavored Markdown -> Slack mrkdwn
+// 完全な変換はしない. release note でよく使うものだけ
+fn markdown2mrkdwn(markdown: &str) -> String {
+    let mut in_code_block = false;
+    markdown
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code_block = !in_code_block;
+                return "```".to_string();
+            }
+            // 本文の <!channel> などを mention や link にしないよう, 変換の前に escape する
+            let line = slack::escape(line);
+            if in_code_block {
+                return line;
+            }
+
+            let line = HEADING.replace(&line, "*$1*");
+            let line = LIST.replace(&line, "$1• ");
+            let line = BOLD.replace_all(&line, "*$1$2*");
+            let line = STRIKE.replace_all(&line, "~$1~");
+            let line = LINK.replace_all(&line, "<$2|$1>");
+            line.into_owned()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// header block には link を張れないので, link 付きの title は section にする
+fn title(title: &str, link: &url::Url) -> slack::Block {
+    slack::Block::section(&format!("*<{link}|{}>*", slack::escape(title)))
+}
+
+// repo, author の avatar, label を並べた context
+fn context(
+    repo: &github::common::Repository,
+    author: Option<&github::common::User>,
+    labels: &[github::common::Label],
+) -> slack::Block {
+    let mut elements = vec![];
+    if let Some(user) = author {
+        elements.push(slack::ContextElement::Image {
+            image_url: user.avatar_url.clone(),
+            alt_text: user.login.clone(),
+        });
+        elements.push(slack::ContextElement::Mrkdwn {
+            text: user.login.clone(),
+        });
+    }
+    elements.push(slack::ContextElement::Mrkdwn {
+        text: format!("<{}|{}>", repo.html_url, repo.full_name),
+    });
+    if let Some(labels) = labels2str(labels) {
+        elements.push(slack::ContextElement::Mrkdwn { text: labels });
+    }
+    slack::Block::Context { elements }
+}
+
+// title, 本文, context の順に並べた attachment
+fn card(
+    title: Option<slack::Block>,
+    body: &str,
+    context: slack::Block,
+    fallback: String,
+    color: slack::Color,
+) -> slack::Attachment {
+    let mut blocks: Vec<slack::Block> = title.into_iter().collect();
+    if !body.is_empty() {
+        blocks.push(slack::Block::section(body));
+    }
+    blocks.push(context);
+    slack::Attachment {
+        fallback,
+        color: Some(color),
+        blocks,
+    }
+}
+
+fn issue_attachment(
+    repo: &github::common::Repository,
+    issue: &github::common::Issue,
+    text: String,
+    color: slack::Color,
+) -> slack::Attachment {
+    card(
+        Some(title(
+            &format!("#{} {}", issue.number, issue.title),
+            &issue.html_url,
+        )),
+        &text,
+        context(repo, Some(&issue.user), &issue.labels),
+        issue.title.to_string(),
+        color,
+    )
+}
+
+fn pr_attachment(
+    repo: &github::common::Repository,
+    pr: &github::common::PullRequest,
+    text: String,
+    color: slack::Color,
+) -> slack::Attachment {
+    card(
+        Some(title(&format!("#{} {}", pr.number, pr.title), &pr.html_url)),
+        &text,
+        context(repo, Some(&pr.user), &pr.labels),
+        pr.title.to_string(),
+        color,
+    )
+}
+
+// 状態が変わっていれば title の後ろに badge を付ける
+fn state_title(title: &str, link: &url::Url, badge: Option<&str>) -> slack::Block {
+    match badge {
+        Some(badge) => {
+            slack::Block::section(&format!("*<{link}|{}>* `{badge}`", slack::escape(title)))
+        }
+        None => self::title(title, link),
+    }
+}
+
+// issue を開いた時の message. 後から状態が変わったら今の状態で作り直して書き換える
+fn issue_opened(
+    repo: &github::common::Repository,
+    issue: &github::common::Issue,
+) -> slack::Message {
+    let (badge, color) = match (issue.state.as_str(), issue.state_reason.as_deref()) {
+        ("closed", Some("not_planned")) => {
+            (Some("Closed as not planned"), slack::Color::NotPlanned)
+        }
+        ("closed", _) => (Some("Closed"), slack::Color::Closed),
+        _ => (None, slack::Color::Good),
+    };
+
+    // ここにユーザへのリンクを入れるとGitHub Appが破壊するので入れない(#13)
+    let text = format!(
+        "[{repo}] Issue created by {user}",
+        repo = repo.full_name,
+        user = issue.user.login
+    );
+
+    let mut body = slack::escape(issue.body.as_deref().unwrap_or_default());
+    if let Some(astr) = users2str(&issue.assignees, "\n", true) {
+        body += "\n*Assignees*\n";
+        body += &astr;
+    }
+    let attach = card(
+        Some(state_title(
+            &format!("#{} {}", issue.number, issue.title),
+            &issue.html_url,
+            badge,
+        )),
+        &body,
+        context(repo, Some(&issue.user), &issue.labels),
+        format!(
+            "{title}\n{body}",
+            title = issue.title,
+            body = issue.body.as_deref().unwrap_or_default()
+        ),
+        color,
+    );
+
+    slack::Message {
+        text,
+        attachments: Some(vec![attach]),
+    }
+}
+
+// PR を開いた時の message. issue_opened と同じく書き換えに使う
+fn pr_opened(
+    repo: &github::common::Repository,
+    pr: &github::common::PullRequest,
+) -> slack::Message {
+    let (badge, color) = if pr.merged == Some(true) {
+        (Some("Merged"), slack::Color::Merged)
+    } else if pr.state == "closed" {
+        (Some("Closed"), slack::Color::Closed)
+    } else {
+        (None, slack::Color::Good)
+    };
+
+    let text = format!(
+        "[{repo}] Pull Request opened by {user}",
+        repo = repo.full_name,
+        user = pr.user.login
+    );
+
+    let mut body = slack::escape(&pr.body);
+    if let Some(astr) = users2str(&pr.assignees, "\n", true) {
+        body += "\n*Assignees*\n";
+        body += &astr;
+    }
+    let attach = card(
+        Some(state_title(
+            &format!("#{} {}", pr.number, pr.title),
+            &pr.html_url,
+            badge,
+        )),
+        &body,
+        context(repo, Some(&pr.user), &pr.labels),
+        format!("{title}\n{body}", title = pr.title, body = pr.body),
+        color,
+    );
+
+    slack::Message {
+        text,
+        attachments: Some(vec![attach]),
+    }
+}
+
+// thread の親 (issue / PR を開いた message) を今の状態で作り直す. chat.update 用
+pub fn opening(payload: &github::Payload) -> Option<slack::Message> {
+    match payload {
+        github::Payload::Issues(issues) => Some(issue_opened(&issues.repository, &issues.issue)),
+        github::Payload::PullRequest(pr) => Some(pr_opened(&pr.repository, &pr.pull_request)),
+        _ => None,
+    }
+}
+
 impl TryFrom<&github::Issues> for slack::Message {
-    type Error = ();
+    type Error = Error;
 
     fn try_from(issues: &github::Issues) -> Result<Self, Self::Error> {
         let repo = &issues.repository;
         let issue = &issues.issue;
-        let user = &issue.user;
+        let sender = &issues.sender;
 
         match issues.action {
             github::IssuesAction::Opened => {
@@ -64,45 +367,7 @@ impl TryFrom<&github::Issues> for slack::Message {
                     info!("IssuesAction::Opened: issue.assignee = {assign}");
                 }
 
-                // ここにユーザへのリンクを入れるとGitHub Appが破壊するので入れない(#13)
-                let text = format!(
-                    "[{repo}] Issue created by {user}",
-                    repo = repo.full_name,
-                    user = user.login
-                );
-
-                let attach = {
-                    let color = Some(slack::Color::Good);
-                    let title = Some(format!(
-                        "#{number} {title}",
-                        number = issue.number,
-                        title = issue.title
-                    ));
-                    let title_link = Some(issue.html_url.clone());
-
-                    let mut text: String = issue.body.clone().unwrap_or_default();
-                    if let Some(astr) = users2str(&issue.assignees, "\n", true) {
-                        text += "\n*Assignees*\n";
-                        text += &astr;
-                    }
-
-                    let fallback = format!(
-                        "{title}\n{body}",
-                        title = issue.title,
-                        body = if let Some(b) = &issue.body { b } else { "" }
-                    );
-
-                    slack::Attachment {
-                        title,
-                        title_link,
-                        fallback,
-                        text,
-                        color,
-                    }
-                };
-                let attachments = Some(vec![attach]);
-
-                Ok(Self { text, attachments })
+                Ok(issue_opened(repo, issue))
             }
             github::IssuesAction::Assigned => {
                 // enterpriseでなければこっちに入る？
@@ -115,133 +380,294 @@ impl TryFrom<&github::Issues> for slack::Message {
                 }
 
                 let assignees = &issue.assignees;
-                assert!(!assignees.is_empty());
+                if assignees.is_empty() {
+                    return Err(Error::MissingField("issue.assignees"));
+                }
 
                 let text = format!(
                     "[{}] Issue assigned to {}",
                     repo.full_name,
-                    users2str(assignees, ",", false).expect("no assignees on issue assigned event")
+                    users2str(assignees, ",", false).unwrap_or_default()
                 );
 
                 let attach = {
-                    let color = Some(slack::Color::Good);
-                    let title = Some(format!(
-                        "#{number} {title}",
-                        number = issue.number,
-                        title = issue.title
-                    ));
-                    let title_link = Some(issue.html_url.clone());
-                    let fallback = issue.title.to_string();
-
                     let text = "*Assignees*\n".to_string()
-                        + &users2str(assignees, "\n", true)
-                            .expect("no assignees on issue assigned event");
+                        + &users2str(assignees, "\n", true).unwrap_or_default();
+                    issue_attachment(repo, issue, text, slack::Color::Good)
+                };
+                let attachments = Some(vec![attach]);
 
-                    slack::Attachment {
-                        title,
-                        title_link,
-                        fallback,
-                        text,
-                        color,
-                    }
+                Ok(Self { text, attachments })
+            }
+            github::IssuesAction::Closed => {
+                let (reason, color) = match issue.state_reason.as_deref() {
+                    Some("not_planned") => (" as not planned", slack::Color::NotPlanned),
+                    Some("completed") => (" as completed", slack::Color::Closed),
+                    _ => ("", slack::Color::Closed),
+                };
+                let text = format!(
+                    "[{repo}] Issue closed{reason} by {sender}",
+                    repo = repo.full_name,
+                    sender = sender.login
+                );
+                let attach = issue_attachment(repo, issue, String::new(), color);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::IssuesAction::Reopened => {
+                let text = format!(
+                    "[{repo}] Issue reopened by {sender}",
+                    repo = repo.full_name,
+                    sender = sender.login
+                );
+                let attach = issue_attachment(repo, issue, String::new(), slack::Color::Good);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::IssuesAction::Labeled | github::IssuesAction::Unlabeled => {
+                // label 自体が削除された場合は label が入ってこないので通知しない
+                let label = issues.label.as_ref().ok_or_else(|| {
+                    Error::Unsupported(format!("issues.{:?} of a deleted label", issues.action))
+                })?;
+                let verb = if issues.action == github::IssuesAction::Labeled {
+                    "added to"
+                } else {
+                    "removed from"
                 };
+                let text = format!(
+                    "[{repo}] Label `{label}` {verb} issue by {sender}",
+                    repo = repo.full_name,
+                    label = slack::escape(&label.name),
+                    sender = sender.login
+                );
+                // label は context に並ぶ
+                let attach = issue_attachment(repo, issue, String::new(), slack::Color::Comment);
                 let attachments = Some(vec![attach]);
 
                 Ok(Self { text, attachments })
             }
-            _ => Err(()),
+            github::IssuesAction::Milestoned => {
+                let milestone = issues
+                    .milestone
+                    .as_ref()
+                    .or(issue.milestone.as_ref())
+                    .ok_or(Error::MissingField("milestone"))?;
+                let text = format!(
+                    "[{repo}] Issue added to milestone <{link}|{title}> by {sender}",
+                    repo = repo.full_name,
+                    link = milestone.html_url,
+                    title = slack::escape(&milestone.title),
+                    sender = sender.login
+                );
+                let attach = issue_attachment(repo, issue, String::new(), slack::Color::Comment);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::IssuesAction::Transferred => {
+                let changes = issues
+                    .changes
+                    .as_ref()
+                    .ok_or(Error::MissingField("changes"))?;
+                let new_repo = changes
+                    .new_repository
+                    .as_ref()
+                    .ok_or(Error::MissingField("changes.new_repository"))?;
+                let text = format!(
+                    "[{repo}] Issue transferred to {new_repo} by {sender}",
+                    repo = repo.full_name,
+                    new_repo = new_repo.full_name,
+                    sender = sender.login
+                );
+
+                // 移動後の issue にリンクする
+                let issue = changes.new_issue.as_ref().unwrap_or(issue);
+                let attach = issue_attachment(repo, issue, String::new(), slack::Color::Comment);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::IssuesAction::Pinned => {
+                let text = format!(
+                    "[{repo}] Issue pinned by {sender}",
+                    repo = repo.full_name,
+                    sender = sender.login
+                );
+                let attach = issue_attachment(repo, issue, String::new(), slack::Color::Comment);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::IssuesAction::Locked => {
+                let text = format!(
+                    "[{repo}] Issue locked by {sender}",
+                    repo = repo.full_name,
+                    sender = sender.login
+                );
+                let reason = issue
+                    .active_lock_reason
+                    .as_ref()
+                    .map(|r| format!("*Reason*\n{r}"))
+                    .unwrap_or_default();
+                let attach = issue_attachment(repo, issue, reason, slack::Color::Warning);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            _ => Err(Error::Unsupported(format!("issues.{:?}", issues.action))),
         }
     }
 }
 
 impl TryFrom<&github::PullRequest> for slack::Message {
-    type Error = ();
+    type Error = Error;
 
     fn try_from(pull_request: &github::PullRequest) -> Result<Self, Self::Error> {
         let repo = &pull_request.repository;
         let pr = &pull_request.pull_request;
+        let sender = &pull_request.sender;
 
         match pull_request.action {
-            github::PullRequestAction::Opened => {
+            github::PullRequestAction::Opened => Ok(pr_opened(repo, pr)),
+
+            github::PullRequestAction::Assigned => {
+                let assignees = &pr.assignees;
+                if assignees.is_empty() {
+                    return Err(Error::MissingField("pull_request.assignees"));
+                }
+
+                let text = {
+                    let repo = &repo.full_name;
+                    let assignees = users2str(assignees, ",", false).unwrap_or_default();
+                    format!("[{repo}] Pull Request assigned to {assignees}",)
+                };
+
+                let attach = {
+                    let text = "*Assignees*\n".to_string()
+                        + &users2str(assignees, "\n", true).unwrap_or_default();
+                    pr_attachment(repo, pr, text, slack::Color::Good)
+                };
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::PullRequestAction::Closed if pr.merged == Some(true) => {
+                let merged_by = pr.merged_by.as_ref().unwrap_or(sender);
                 let text = format!(
-                    "[{repo}] Pull Request opened by {user}",
+                    "[{repo}] Pull Request merged by {user}",
                     repo = repo.full_name,
-                    user = pr.user.login
+                    user = merged_by.login
                 );
 
                 let attach = {
-                    let color = Some(slack::Color::Good);
-
-                    let title = Some(format!(
-                        "#{number} {title}",
-                        number = pr.number,
-                        title = pr.title
-                    ));
-                    let title_link = Some(pr.html_url.clone());
-                    let fallback = format!("{title}\n{body}", title = pr.title, body = pr.body);
-
-                    let mut text = pr.body.clone();
-                    if let Some(astr) = users2str(&pr.assignees, "\n", true) {
-                        text += "\n*Assignees*\n";
-                        text += &astr;
-                    }
-
-                    slack::Attachment {
-                        title,
-                        title_link,
-                        fallback,
-                        text,
-                        color,
-                    }
+                    let text = match &pr.merge_commit_sha {
+                        Some(sha) => format!(
+                            "Merged <{repo_link}/commit/{sha}|`{short}`> into `{base}`",
+                            repo_link = repo.html_url.as_str().trim_end_matches('/'),
+                            short = &sha[..sha.len().min(7)],
+                            base = slack::escape(&pr.base.ref_)
+                        ),
+                        None => format!("Merged into `{}`", slack::escape(&pr.base.ref_)),
+                    };
+                    pr_attachment(repo, pr, text, slack::Color::Merged)
                 };
                 let attachments = Some(vec![attach]);
 
                 Ok(Self { text, attachments })
             }
+            github::PullRequestAction::Closed => {
+                let text = format!(
+                    "[{repo}] Pull Request closed without merge by {user}",
+                    repo = repo.full_name,
+                    user = sender.login
+                );
+                let attach = pr_attachment(repo, pr, String::new(), slack::Color::Closed);
+                let attachments = Some(vec![attach]);
 
-            github::PullRequestAction::Assigned => {
-                let assignees = &pr.assignees;
-                assert!(!assignees.is_empty());
+                Ok(Self { text, attachments })
+            }
+            github::PullRequestAction::Reopened => {
+                let text = format!(
+                    "[{repo}] Pull Request reopened by {user}",
+                    repo = repo.full_name,
+                    user = sender.login
+                );
+                let attach = pr_attachment(repo, pr, String::new(), slack::Color::Good);
+                let attachments = Some(vec![attach]);
 
-                let text = {
-                    let repo = &repo.full_name;
-                    let assignees = users2str(assignees, ",", false)
-                        .expect("no assignees on issue assigned event");
-                    format!("[{repo}] Pull Request assigned to {assignees}",)
-                };
+                Ok(Self { text, attachments })
+            }
+            github::PullRequestAction::ConvertedToDraft => {
+                let text = format!(
+                    "[{repo}] Pull Request converted to draft by {user}",
+                    repo = repo.full_name,
+                    user = sender.login
+                );
+                let attach = pr_attachment(repo, pr, String::new(), slack::Color::NotPlanned);
+                let attachments = Some(vec![attach]);
 
-                let attach = {
-                    let title = Some(format!(
-                        "#{number} {title}",
-                        number = pr.number,
-                        title = pr.title
-                    ));
-                    let title_link = Some(pr.html_url.clone());
-                    let text = "*Assignees*\n".to_string()
-                        + &users2str(assignees, "\n", true)
-                            .expect("no assignees on puull request assigned event");
+                Ok(Self { text, attachments })
+            }
+            github::PullRequestAction::ReadyForReview => {
+                let text = format!(
+                    "[{repo}] Pull Request is ready for review by {user}",
+                    repo = repo.full_name,
+                    user = sender.login
+                );
+                let attach = pr_attachment(repo, pr, String::new(), slack::Color::Good);
+                let attachments = Some(vec![attach]);
 
-                    let color = Some(slack::Color::Good);
+                Ok(Self { text, attachments })
+            }
+            github::PullRequestAction::ReviewRequested => {
+                let reviewer = if let Some(user) = &pull_request.requested_reviewer {
+                    user.login.to_string()
+                } else if let Some(team) = &pull_request.requested_team {
+                    slack::escape(&team.name)
+                } else {
+                    return Err(Error::MissingField("requested_reviewer"));
+                };
+                let text = format!(
+                    "[{repo}] Review requested from {reviewer} by {user}",
+                    repo = repo.full_name,
+                    user = sender.login
+                );
+                let attach = pr_attachment(repo, pr, String::new(), slack::Color::Good);
+                let attachments = Some(vec![attach]);
 
-                    slack::Attachment {
-                        title,
-                        title_link,
-                        fallback: pr.title.to_string(),
-                        text,
-                        color,
-                    }
+                Ok(Self { text, attachments })
+            }
+            github::PullRequestAction::AutoMergeEnabled => {
+                let auto_merge = pr
+                    .auto_merge
+                    .as_ref()
+                    .ok_or(Error::MissingField("pull_request.auto_merge"))?;
+                let enabled_by = auto_merge.enabled_by.as_ref().unwrap_or(sender);
+                let text = format!(
+                    "[{repo}] Auto-merge enabled by {user}",
+                    repo = repo.full_name,
+                    user = enabled_by.login
+                );
+                let attach = {
+                    let text = format!("*Merge method*\n{}", auto_merge.merge_method);
+                    pr_attachment(repo, pr, text, slack::Color::Good)
                 };
                 let attachments = Some(vec![attach]);
 
                 Ok(Self { text, attachments })
             }
-            _ => Err(()),
+            _ => Err(Error::Unsupported(format!(
+                "pull_request.{:?}",
+                pull_request.action
+            ))),
         }
     }
 }
 
 impl TryFrom<&github::IssueComment> for slack::Message {
-    type Error = ();
+    type Error = Error;
 
     fn try_from(issue_comment: &github::IssueComment) -> Result<Self, Self::Error> {
         let repo = &issue_comment.repository;
@@ -252,9 +678,7 @@ impl TryFrom<&github::IssueComment> for slack::Message {
 
         match issue_comment.action {
             github::IssueCommentAction::Created => {
-                let color = Some(slack::Color::Comment);
-
-                let typ = if issue.is_pull_request() {
+                let typ = if issue_comment.is_pull_request() {
                     "pull request"
                 } else {
                     "issue"
@@ -263,20 +687,963 @@ impl TryFrom<&github::IssueComment> for slack::Message {
                     "[{repo_name}] New comment by {username} on {typ} <{ic_link}|#{number}: {title}>",
                     repo_name = repo.full_name,
                     number = issue.number,
-                    title = issue.title
+                    title = slack::escape(&issue.title)
+                );
+                let attach = card(
+                    None,
+                    &slack::escape(&comment.body),
+                    context(repo, Some(&comment.user), &[]),
+                    comment.body.clone(),
+                    slack::Color::Comment,
                 );
-                let attach = slack::Attachment {
-                    title: None,
-                    title_link: None,
-                    fallback: comment.body.clone(),
-                    text: comment.body.clone(),
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            _ => Err(Error::Unsupported(format!(
+                "issue_comment.{:?}",
+                issue_comment.action
+            ))),
+        }
+    }
+}
+
+impl TryFrom<&github::PullRequestReview> for slack::Message {
+    type Error = Error;
+
+    fn try_from(pull_request_review: &github::PullRequestReview) -> Result<Self, Self::Error> {
+        use github::common::ReviewState;
+
+        let repo = &pull_request_review.repository;
+        let pr = &pull_request_review.pull_request;
+        let review = &pull_request_review.review;
+        let review_link = &review.html_url;
+        let username = &review.user.login;
+
+        match pull_request_review.action {
+            github::PullRequestReviewAction::Submitted => {
+                let body = review.body.clone().unwrap_or_default();
+
+                let (verb, color) = match review.state {
+                    ReviewState::Approved => ("approved", slack::Color::Good),
+                    ReviewState::ChangesRequested => ("requested changes on", slack::Color::Danger),
+                    // inline comment への返信でも本文なしの review が飛んでくるので無視する
+                    ReviewState::Commented if body.is_empty() => {
+                        return Err(Error::Unsupported("empty review".to_string()))
+                    }
+                    ReviewState::Commented => ("reviewed", slack::Color::Comment),
+                    ReviewState::Dismissed => {
+                        return Err(Error::Unsupported("dismissed review".to_string()))
+                    }
+                };
+
+                let text = format!(
+                    "[{repo_name}] {username} {verb} pull request <{review_link}|#{number}: {title}>",
+                    repo_name = repo.full_name,
+                    number = pr.number,
+                    title = slack::escape(&pr.title)
+                );
+                let attach = card(
+                    None,
+                    &slack::escape(&body),
+                    context(repo, Some(&review.user), &[]),
+                    body.clone(),
                     color,
+                );
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            _ => Err(Error::Unsupported(format!(
+                "pull_request_review.{:?}",
+                pull_request_review.action
+            ))),
+        }
+    }
+}
+
+// diff_hunk は hunk の先頭から comment 対象行までなので、末尾だけ残す
+const DIFF_HUNK_MAX_LINES: usize = 8;
+
+impl TryFrom<&github::PullRequestReviewComment> for slack::Message {
+    type Error = Error;
+
+    fn try_from(review_comment: &github::PullRequestReviewComment) -> Result<Self, Self::Error> {
+        let repo = &review_comment.repository;
+        let pr = &review_comment.pull_request;
+        let comment = &review_comment.comment;
+        let username = &comment.user.login;
+
+        match review_comment.action {
+            github::PullRequestReviewCommentAction::Created => {
+                let what = if comment.in_reply_to_id.is_some() {
+                    "reply"
+                } else {
+                    "review comment"
+                };
+                let text = format!(
+                    "[{repo_name}] New {what} by {username} on pull request <{pr_link}|#{number}: {title}>",
+                    repo_name = repo.full_name,
+                    pr_link = pr.html_url,
+                    number = pr.number,
+                    title = slack::escape(&pr.title)
+                );
+
+                let line = comment.line.or(comment.original_line);
+                let start_line = comment.start_line.or(comment.original_start_line);
+                let title = match (start_line, line) {
+                    (Some(s), Some(l)) if s != l => format!("{}:L{s}-L{l}", comment.path),
+                    (_, Some(l)) => format!("{}:L{l}", comment.path),
+                    _ => comment.path.clone(),
+                };
+
+                let hunk: Vec<&str> = comment.diff_hunk.lines().collect();
+                // code block の中でも <, >, & は Slack に解釈される
+                let hunk = slack::escape(
+                    &hunk[hunk.len().saturating_sub(DIFF_HUNK_MAX_LINES)..].join("\n"),
+                );
+                let attach = {
+                    let mut blocks = vec![
+                        self::title(&title, &comment.html_url),
+                        slack::Block::section(&format!("```\n{hunk}\n```")),
+                    ];
+                    if !comment.body.is_empty() {
+                        blocks.push(slack::Block::Divider);
+                        blocks.push(slack::Block::section(&slack::escape(&comment.body)));
+                    }
+                    blocks.push(context(repo, Some(&comment.user), &[]));
+                    slack::Attachment {
+                        fallback: comment.body.clone(),
+                        color: Some(slack::Color::Comment),
+                        blocks,
+                    }
+                };
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            _ => Err(Error::Unsupported(format!(
+                "pull_request_review_comment.{:?}",
+                review_comment.action
+            ))),
+        }
+    }
+}
+
+// これより多い commit は省略する
+const PUSH_MAX_COMMITS: usize = 10;
+
+fn commits2str(commits: &[github::common::Commit]) -> String {
+    let mut lines: Vec<String> = commits
+        .iter()
+        .take(PUSH_MAX_COMMITS)
+        .map(|c| {
+            let short = &c.id[..c.id.len().min(7)];
+            let summary = slack::escape(c.message.lines().next().unwrap_or_default());
+            format!(
+                "<{url}|`{short}`> {summary} - {author}",
+                url = c.url,
+                author = slack::escape(&c.author.name)
+            )
+        })
+        .collect();
+
+    if commits.len() > PUSH_MAX_COMMITS {
+        lines.push(format!(
+            "... and {} more commits",
+            commits.len() - PUSH_MAX_COMMITS
+        ));
+    }
+
+    lines.join("\n")
+}
+
+impl TryFrom<&github::Push> for slack::Message {
+    type Error = Error;
+
+    fn try_from(push: &github::Push) -> Result<Self, Self::Error> {
+        let repo = &push.repository;
+        let sender = &push.sender;
+
+        // tag の push や branch の作成・削除は create/delete event で扱う
+        let branch = push
+            .branch()
+            .ok_or_else(|| Error::Unsupported(format!("push to {}", push.ref_)))?;
+        if push.deleted || push.commits.is_empty() {
+            return Err(Error::Unsupported(format!(
+                "push without commits to {branch}"
+            )));
+        }
+
+        let text = format!(
+            "[{repo}] {user} {verb} {n} commit{s} to `{branch}`",
+            repo = repo.full_name,
+            user = sender.login,
+            verb = if push.forced {
+                "force-pushed"
+            } else {
+                "pushed"
+            },
+            branch = slack::escape(branch),
+            n = push.commits.len(),
+            s = if push.commits.len() == 1 { "" } else { "s" },
+        );
+
+        let attach = {
+            let color = if push.forced {
+                slack::Color::Warning
+            } else {
+                slack::Color::Good
+            };
+            let title = title(
+                &format!(
+                    "{before}...{after}",
+                    before = &push.before[..push.before.len().min(7)],
+                    after = &push.after[..push.after.len().min(7)]
+                ),
+                &push.compare,
+            );
+
+            let fallback = push
+                .commits
+                .iter()
+                .map(|c| c.message.lines().next().unwrap_or_default())
+                .collect::<Vec<&str>>()
+                .join("\n");
+
+            card(
+                Some(title),
+                &commits2str(&push.commits),
+                context(repo, Some(sender), &[]),
+                fallback,
+                color,
+            )
+        };
+        let attachments = Some(vec![attach]);
+
+        Ok(Self { text, attachments })
+    }
+}
+
+impl TryFrom<&github::Release> for slack::Message {
+    type Error = Error;
+
+    fn try_from(release: &github::Release) -> Result<Self, Self::Error> {
+        let repo = &release.repository;
+        let r = &release.release;
+        let name = r
+            .name
+            .as_deref()
+            .filter(|n| !n.is_empty())
+            .unwrap_or(&r.tag_name);
+
+        let (what, color) = match release.action {
+            // pre-release は published と prereleased の両方が来るので prereleased だけ通知する
+            github::ReleaseAction::Published if r.prerelease => {
+                return Err(Error::Unsupported("published pre-release".to_string()))
+            }
+            github::ReleaseAction::Published => ("published", slack::Color::Good),
+            github::ReleaseAction::Prereleased => ("published", slack::Color::Warning),
+            github::ReleaseAction::Edited => ("edited", slack::Color::Comment),
+            _ => return Err(Error::Unsupported(format!("release.{:?}", release.action))),
+        };
+        let text = format!(
+            "[{repo}] {typ} {name} {what} by {user}",
+            repo = repo.full_name,
+            name = slack::escape(name),
+            typ = if r.prerelease {
+                "Pre-release"
+            } else {
+                "Release"
+            },
+            user = r.author.login
+        );
+
+        let attach = {
+            let mut text = format!("*Tag* `{}`", slack::escape(&r.tag_name));
+            if r.prerelease {
+                text += " (pre-release)";
+            }
+            if let Some(body) = r.body.as_deref().filter(|b| !b.is_empty()) {
+                text += "\n";
+                text += &markdown2mrkdwn(body);
+            }
+
+            // release 名は目立つように header にして, link は button にする
+            slack::Attachment {
+                fallback: format!("{name}\n{}", r.body.as_deref().unwrap_or_default()),
+                color: Some(color),
+                blocks: vec![
+                    slack::Block::header(name),
+                    slack::Block::section(&text),
+                    slack::Block::button("View release", r.html_url.clone()),
+                    context(repo, Some(&r.author), &[]),
+                ],
+            }
+        };
+        let attachments = Some(vec![attach]);
+
+        Ok(Self { text, attachments })
+    }
+}
+
+fn ref_type2str(ref_type: &github::RefType) -> &'static str {
+    match ref_type {
+        github::RefType::Tag => "Tag",
+        github::RefType::Branch => "Branch",
+    }
+}
+
+impl TryFrom<&github::Create> for slack::Message {
+    type Error = Error;
+
+    fn try_from(create: &github::Create) -> Result<Self, Self::Error> {
+        let repo = &create.repository;
+
+        let text = format!(
+            "[{repo}] {typ} <{repo_link}/tree/{ref_}|`{ref_}`> created by {user}",
+            repo = repo.full_name,
+            typ = ref_type2str(&create.ref_type),
+            repo_link = repo.html_url.as_str().trim_end_matches('/'),
+            ref_ = slack::escape(&create.ref_),
+            user = create.sender.login
+        );
+
+        Ok(Self {
+            text,
+            attachments: None,
+        })
+    }
+}
+
+impl TryFrom<&github::Delete> for slack::Message {
+    type Error = Error;
+
+    fn try_from(delete: &github::Delete) -> Result<Self, Self::Error> {
+        let text = format!(
+            "[{repo}] {typ} `{ref_}` deleted by {user}",
+            repo = delete.repository.full_name,
+            typ = ref_type2str(&delete.ref_type),
+            ref_ = slack::escape(&delete.ref_),
+            user = delete.sender.login
+        );
+
+        Ok(Self {
+            text,
+            attachments: None,
+        })
+    }
+}
+
+// 失敗した時だけ通知する
+fn conclusion2color(conclusion: &github::common::Conclusion) -> Option<slack::Color> {
+    use github::common::Conclusion;
+
+    match conclusion {
+        Conclusion::Failure | Conclusion::TimedOut | Conclusion::StartupFailure => {
+            Some(slack::Color::Danger)
+        }
+        Conclusion::Cancelled => Some(slack::Color::Warning),
+        _ => None,
+    }
+}
+
+impl TryFrom<&github::WorkflowRun> for slack::Message {
+    type Error = Error;
+
+    fn try_from(workflow_run: &github::WorkflowRun) -> Result<Self, Self::Error> {
+        let repo = &workflow_run.repository;
+        let run = &workflow_run.workflow_run;
+
+        if workflow_run.action != github::WorkflowRunAction::Completed {
+            return Err(Error::Unsupported(format!(
+                "workflow_run.{:?}",
+                workflow_run.action
+            )));
+        }
+        let conclusion = run
+            .conclusion
+            .as_ref()
+            .ok_or(Error::MissingField("workflow_run.conclusion"))?;
+        let color = conclusion2color(conclusion)
+            .ok_or_else(|| Error::Unsupported(format!("workflow_run {conclusion}")))?;
+
+        let branch = slack::escape(run.head_branch.as_deref().unwrap_or("-"));
+        let actor = run.actor.as_ref().unwrap_or(&workflow_run.sender);
+        let text = format!(
+            "[{repo}] Workflow {workflow} {conclusion} on `{branch}` (triggered by {user})",
+            repo = repo.full_name,
+            workflow = slack::escape(&workflow_run.workflow.name),
+            user = actor.login
+        );
+
+        let attach = card(
+            Some(title(
+                &format!(
+                    "{workflow} #{number}: {title}",
+                    workflow = workflow_run.workflow.name,
+                    number = run.run_number,
+                    title = run.display_title
+                ),
+                &run.html_url,
+            )),
+            &format!("*Commit* `{}`", &run.head_sha[..run.head_sha.len().min(7)]),
+            context(repo, Some(actor), &[]),
+            format!("{} {conclusion}", workflow_run.workflow.name),
+            color,
+        );
+        let attachments = Some(vec![attach]);
+
+        Ok(Self { text, attachments })
+    }
+}
+
+impl TryFrom<&github::CheckSuite> for slack::Message {
+    type Error = Error;
+
+    fn try_from(check_suite: &github::CheckSuite) -> Result<Self, Self::Error> {
+        let repo = &check_suite.repository;
+        let suite = &check_suite.check_suite;
+
+        // GitHub Actions の結果は workflow_run で通知する
+        if suite.app.slug.as_deref() == Some("github-actions") {
+            return Err(Error::Unsupported(
+                "check_suite by GitHub Actions".to_string(),
+            ));
+        }
+        if check_suite.action != github::CheckSuiteAction::Completed {
+            return Err(Error::Unsupported(format!(
+                "check_suite.{:?}",
+                check_suite.action
+            )));
+        }
+        let conclusion = suite
+            .conclusion
+            .as_ref()
+            .ok_or(Error::MissingField("check_suite.conclusion"))?;
+        let color = conclusion2color(conclusion)
+            .ok_or_else(|| Error::Unsupported(format!("check_suite {conclusion}")))?;
+
+        let branch = slack::escape(suite.head_branch.as_deref().unwrap_or("-"));
+        let text = format!(
+            "[{repo}] Check suite {app} {conclusion} on `{branch}` (triggered by {user})",
+            repo = repo.full_name,
+            app = slack::escape(&suite.app.name),
+            user = check_suite.sender.login
+        );
+
+        // check suite には html_url が無いので commit の checks にリンクする
+        let sha = &suite.head_sha;
+        let link = url::Url::parse(&format!(
+            "{repo_link}/commit/{sha}/checks",
+            repo_link = repo.html_url.as_str().trim_end_matches('/')
+        ))
+        .map_err(|e| Error::RenderFailure(e.to_string()))?;
+        let attach = card(
+            Some(title(
+                &format!("{} `{}`", suite.app.name, &sha[..sha.len().min(7)]),
+                &link,
+            )),
+            "",
+            context(repo, Some(&check_suite.sender), &[]),
+            format!("{} {conclusion}", suite.app.name),
+            color,
+        );
+        let attachments = Some(vec![attach]);
+
+        Ok(Self { text, attachments })
+    }
+}
+
+fn discussion_attachment(
+    repo: &github::common::Repository,
+    discussion: &github::common::Discussion,
+    text: String,
+    color: slack::Color,
+) -> slack::Attachment {
+    let category = &discussion.category;
+    card(
+        Some(title(
+            &format!("#{} {}", discussion.number, discussion.title),
+            &discussion.html_url,
+        )),
+        format!(
+            "{text}\n*Category* {emoji} {name}",
+            emoji = category.emoji,
+            name = slack::escape(&category.name)
+        )
+        .trim_start(),
+        context(repo, Some(&discussion.user), &discussion.labels),
+        discussion.title.to_string(),
+        color,
+    )
+}
+
+impl TryFrom<&github::Discussion> for slack::Message {
+    type Error = Error;
+
+    fn try_from(discussion: &github::Discussion) -> Result<Self, Self::Error> {
+        let repo = &discussion.repository;
+        let d = &discussion.discussion;
+        let sender = &discussion.sender;
+
+        match discussion.action {
+            github::DiscussionAction::Created => {
+                let text = format!(
+                    "[{repo}] Discussion created by {user}",
+                    repo = repo.full_name,
+                    user = d.user.login
+                );
+                let body = slack::escape(d.body.as_deref().unwrap_or_default());
+                let attach = discussion_attachment(repo, d, body, slack::Color::Good);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::DiscussionAction::Answered => {
+                let chosen_by = d.answer_chosen_by.as_ref().unwrap_or(sender);
+                let answer = discussion
+                    .answer
+                    .as_ref()
+                    .ok_or(Error::MissingField("answer"))?;
+                let text = format!(
+                    "[{repo}] Discussion answered by {answerer} (chosen by {user})",
+                    repo = repo.full_name,
+                    answerer = answer.user.login,
+                    user = chosen_by.login
+                );
+                let attach = discussion_attachment(
+                    repo,
+                    d,
+                    slack::escape(&answer.body),
+                    slack::Color::Merged,
+                );
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::DiscussionAction::Closed => {
+                let text = format!(
+                    "[{repo}] Discussion closed by {user}",
+                    repo = repo.full_name,
+                    user = sender.login
+                );
+                let attach = discussion_attachment(repo, d, String::new(), slack::Color::Closed);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            github::DiscussionAction::Reopened => {
+                let text = format!(
+                    "[{repo}] Discussion reopened by {user}",
+                    repo = repo.full_name,
+                    user = sender.login
+                );
+                let attach = discussion_attachment(repo, d, String::new(), slack::Color::Good);
+                let attachments = Some(vec![attach]);
+
+                Ok(Self { text, attachments })
+            }
+            _ => Err(Error::Unsupported(format!(
+                "discussion.{:?}",
+                discussion.action
+            ))),
+        }
+    }
+}
+
+impl TryFrom<&github::DiscussionComment> for slack::Message {
+    type Error = Error;
+
+    fn try_from(discussion_comment: &github::DiscussionComment) -> Result<Self, Self::Error> {
+        let repo = &discussion_comment.repository;
+        let d = &discussion_comment.discussion;
+        let comment = &discussion_comment.comment;
+        let dc_link = &comment.html_url;
+        let username = &comment.user.login;
+
+        match discussion_comment.action {
+            github::DiscussionCommentAction::Created => {
+                let what = if comment.parent_id.is_some() {
+                    "reply"
+                } else {
+                    "comment"
                 };
+                let answered = if d.is_answered() { " (answered)" } else { "" };
+                let text = format!(
+                    "[{repo_name}] New {what} by {username} on discussion <{dc_link}|#{number}: {title}>{answered}",
+                    repo_name = repo.full_name,
+                    number = d.number,
+                    title = slack::escape(&d.title)
+                );
+                let attach = card(
+                    None,
+                    &slack::escape(&comment.body),
+                    context(repo, Some(&comment.user), &[]),
+                    comment.body.clone(),
+                    slack::Color::Comment,
+                );
                 let attachments = Some(vec![attach]);
 
                 Ok(Self { text, attachments })
             }
-            _ => Err(()),
+            _ => Err(Error::Unsupported(format!(
+                "discussion_comment.{:?}",
+                discussion_comment.action
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::slack;
+    use crate::test_util::de;
+
+    fn color(msg: &slack::Message) -> Option<&slack::Color> {
+        msg.attachments.as_ref()?.first()?.color.as_ref()
+    }
+
+    fn blocks(msg: &slack::Message) -> &[slack::Block] {
+        &msg.attachments.as_ref().unwrap()[0].blocks
+    }
+
+    // section block の text. title も section なので先頭に入る
+    fn sections(msg: &slack::Message) -> Vec<&str> {
+        blocks(msg)
+            .iter()
+            .filter_map(|b| match b {
+                slack::Block::Section {
+                    text: slack::Text::Mrkdwn { text },
+                } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn context(msg: &slack::Message) -> Vec<&str> {
+        match blocks(msg).last() {
+            Some(slack::Block::Context { elements }) => elements
+                .iter()
+                .map(|e| match e {
+                    slack::ContextElement::Mrkdwn { text } => text.as_str(),
+                    slack::ContextElement::Image { image_url, .. } => image_url.as_str(),
+                })
+                .collect(),
+            b => panic!("the last block is not a context: {b:?}"),
         }
     }
+
+    #[test]
+    fn issues() {
+        let p = de("issues", "issues_closed.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.ends_with("Issue closed as completed by sksat"));
+        assert!(matches!(color(&msg), Some(slack::Color::Closed)));
+
+        let p = de("issues", "issues_closed_not_planned.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.ends_with("Issue closed as not planned by sksat"));
+        assert!(matches!(color(&msg), Some(slack::Color::NotPlanned)));
+
+        let p = de("issues", "issues_labeled.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("Label `bug` added to issue"));
+        assert_eq!(
+            context(&msg),
+            [
+                "https://avatars.githubusercontent.com/u/1000001?v=4",
+                "sksat",
+                "<https://github.com/arkedge/hubhook|arkedge/hubhook>",
+                "`bug`"
+            ]
+        );
+
+        let p = de("issues", "issues_milestoned.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("milestone/1|v1.0.0>"));
+
+        let p = de("issues", "issues_transferred.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("transferred to arkedge/hubhook-config"));
+        assert!(
+            sections(&msg)[0].starts_with("*<https://github.com/arkedge/hubhook-config/issues/12|")
+        );
+
+        let p = de("issues", "issues_locked.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(sections(&msg)[1].contains("resolved"));
+
+        let p = de("issues", "issues_unlabeled_deleted.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(matches!(msg, Err(super::Error::Unsupported(_))));
+
+        // 知らない action は通知しない
+        let p = de("issues", "issues_typed.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(matches!(msg, Err(super::Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn pull_request() {
+        let p = de("pull_request", "pull_request_closed_merged.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.ends_with("Pull Request merged by alice"));
+        assert!(matches!(color(&msg), Some(slack::Color::Merged)));
+        assert!(sections(&msg)[1].contains("`9f3c1b2`> into `main`"));
+
+        let p = de("pull_request", "pull_request_enqueued.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(matches!(msg, Err(super::Error::Unsupported(_))));
+
+        let p = de("pull_request", "pull_request_closed.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("closed without merge"));
+        assert!(matches!(color(&msg), Some(slack::Color::Closed)));
+
+        let p = de("pull_request", "pull_request_converted_to_draft.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("converted to draft"));
+
+        let p = de("pull_request", "pull_request_review_requested.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("Review requested from bob by sksat"));
+
+        let p = de("pull_request", "pull_request_review_requested_team.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("Review requested from Firmware by sksat"));
+
+        let p = de("pull_request", "pull_request_auto_merge_enabled.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("Auto-merge enabled by sksat"));
+        assert!(sections(&msg)[1].contains("squash"));
+    }
+
+    #[test]
+    fn pull_request_review() {
+        let p = de("pull_request_review", "pull_request_review_approved.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("alice approved pull request"));
+        assert!(matches!(color(&msg), Some(slack::Color::Good)));
+
+        let p = de(
+            "pull_request_review",
+            "pull_request_review_changes_requested.json",
+        );
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("bob requested changes on pull request"));
+        assert!(matches!(color(&msg), Some(slack::Color::Danger)));
+
+        // 本文のない commented review は通知しない
+        let p = de("pull_request_review", "pull_request_review_commented.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(matches!(msg, Err(super::Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn pull_request_review_comment() {
+        let p = de(
+            "pull_request_review_comment",
+            "pull_request_review_comment_created.json",
+        );
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("New review comment by bob"));
+        let texts = sections(&msg);
+        assert!(texts[0].ends_with("|src/main.rs:L13>*"));
+        assert!(texts[1].starts_with("```\n@@ -10,6 +10,9 @@"));
+        assert!(texts[1].contains("title: Option&lt;String&gt;,"));
+        assert!(matches!(blocks(&msg)[2], slack::Block::Divider));
+
+        let p = de(
+            "pull_request_review_comment",
+            "pull_request_review_comment_reply.json",
+        );
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("New reply by sksat"));
+
+        let p = de(
+            "pull_request_review_comment",
+            "pull_request_review_comment_escape.json",
+        );
+        let msg: slack::Message = (&p).try_into().unwrap();
+        let hunk = sections(&msg)[1];
+        assert!(hunk.contains("\n+    a &amp;&amp; b\n"));
+        assert!(!hunk.contains("&&"));
+        assert_eq!(
+            sections(&msg)[2],
+            "&lt;!channel&gt; `&amp;&amp;` is intended"
+        );
+    }
+
+    // 本文や title の mention で channel 全体に通知しない
+    #[test]
+    fn escape() {
+        let p = de("issues", "issues_opened_escape.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        let texts = sections(&msg);
+        assert!(texts[0].ends_with("|#1 &lt;!here&gt; A &amp; B>*"));
+        assert_eq!(texts[1], "&lt;!channel&gt; see &lt;@U0123ABCD&gt;");
+        assert_eq!(context(&msg)[3], "`&lt;!subteam&gt;`");
+
+        let p = de("issue_comment", "issue_comment_escape.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.ends_with("|#1: Crash on &lt;!everyone&gt;>"));
+        assert_eq!(sections(&msg)[0], "&lt;!channel&gt; I can reproduce this.");
+    }
+
+    #[test]
+    fn push() {
+        let p = de("push", "push.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.ends_with("sksat pushed 2 commits to `main`"));
+        let commits = sections(&msg)[1];
+        assert!(commits.contains("|`a000000`> commit 1 - sksat"));
+        assert!(!commits.contains("details"));
+
+        let p = de("push", "push_many.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("force-pushed 12 commits to `release/v1`"));
+        assert!(matches!(color(&msg), Some(slack::Color::Warning)));
+        let commits = sections(&msg)[1];
+        assert_eq!(commits.lines().count(), 11);
+        assert!(commits.ends_with("... and 2 more commits"));
+
+        let p = de("push", "push_escape.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(sections(&msg)[1]
+            .ends_with("> Return Option&lt;&amp;str&gt; from &lt;!channel&gt; parser - A &amp; B"));
+
+        // branch の削除や tag の push は create/delete event で通知する
+        let p = de("push", "push_deleted.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(msg.is_err());
+
+        let p = de("push", "push_tag.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(msg.is_err());
+    }
+
+    #[test]
+    fn release() {
+        let p = de("release", "release_published.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.ends_with("Release v1.0.0 published by sksat"));
+        assert!(matches!(blocks(&msg)[0], slack::Block::Header { .. }));
+        assert!(matches!(blocks(&msg)[2], slack::Block::Actions { .. }));
+        let notes = sections(&msg)[0];
+        assert!(notes.starts_with("*Tag* `v1.0.0`\n*What's Changed*\n• "));
+        assert!(notes.contains("• *Breaking*: drop"));
+        assert!(notes
+            .contains("<https://github.com/arkedge/hubhook/blob/main/CHANGELOG.md|the changelog>"));
+
+        let p = de("release", "release_prereleased.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("Pre-release v1.1.0-rc.1 published"));
+        assert!(matches!(color(&msg), Some(slack::Color::Warning)));
+
+        let p = de("release", "release_edited.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("Release v1.0.0 edited"));
+
+        let p = de("create", "create_tag.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg
+            .text
+            .contains("Tag <https://github.com/arkedge/hubhook/tree/v1.0.0|`v1.0.0`> created"));
+
+        let p = de("delete", "delete_branch.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg
+            .text
+            .ends_with("Branch `feature/review` deleted by sksat"));
+    }
+
+    #[test]
+    fn markdown2mrkdwn() {
+        let md = "# Title\n- **bold** and ~~strike~~\n```\n# not heading\n```";
+        assert_eq!(
+            super::markdown2mrkdwn(md),
+            "*Title*\n• *bold* and ~strike~\n```\n# not heading\n```"
+        );
+
+        let md = "<!channel> [a & b](https://example.com/?a=1&b=2) <http://x|y>\n```\nVec<u8>\n```";
+        assert_eq!(
+            super::markdown2mrkdwn(md),
+            "&lt;!channel&gt; <https://example.com/?a=1&amp;b=2|a &amp; b> &lt;http://x|y&gt;\n```\nVec&lt;u8&gt;\n```"
+        );
+    }
+
+    #[test]
+    fn workflow_run() {
+        let p = de("workflow_run", "workflow_run_failure.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg
+            .text
+            .ends_with("Workflow Rust failure on `main` (triggered by sksat)"));
+        assert!(matches!(color(&msg), Some(slack::Color::Danger)));
+        assert!(sections(&msg)[0]
+            .starts_with("*<https://github.com/arkedge/hubhook/actions/runs/9600001|"));
+
+        // 成功は通知しない
+        let p = de("workflow_run", "workflow_run_success.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(msg.is_err());
+    }
+
+    #[test]
+    fn check_suite() {
+        let p = de("check_suite", "check_suite_timed_out.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg
+            .text
+            .contains("Check suite Buildkite timed_out on `main`"));
+        assert!(sections(&msg)[0].starts_with(
+            "*<https://github.com/arkedge/hubhook/commit/0123456789abcdef0123456789abcdef01234567/checks|"
+        ));
+
+        let p = de("check_suite", "check_suite_github_actions.json");
+        let msg: Result<slack::Message, _> = (&p).try_into();
+        assert!(msg.is_err());
+    }
+
+    #[test]
+    fn discussion() {
+        let p = de("discussion", "discussion_created.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.ends_with("Discussion created by sksat"));
+        assert!(sections(&msg)[1].ends_with("*Category* :bulb: RFC"));
+
+        let p = de("discussion", "discussion_answered.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("answered by bob (chosen by sksat)"));
+
+        let p = de("discussion_comment", "discussion_comment_created.json");
+        let msg: slack::Message = (&p).try_into().unwrap();
+        assert!(msg.text.contains("New comment by bob on discussion"));
+    }
+
+    #[test]
+    fn opening() {
+        let p = de("pull_request", "pull_request_opened.json");
+        let msg = super::opening(&p).unwrap();
+        assert!(msg.text.ends_with("Pull Request opened by sksat"));
+        assert!(matches!(color(&msg), Some(slack::Color::Good)));
+        assert!(sections(&msg)[0].ends_with("|#2 Add review notification>*"));
+
+        // 最初の message の text のまま, 色と badge だけ今の状態にする
+        let p = de("pull_request", "pull_request_closed_merged.json");
+        let msg = super::opening(&p).unwrap();
+        assert!(msg.text.contains("Pull Request opened by"));
+        assert!(matches!(color(&msg), Some(slack::Color::Merged)));
+        assert!(sections(&msg)[0].ends_with(">* `Merged`"));
+
+        let p = de("issues", "issues_closed_not_planned.json");
+        let msg = super::opening(&p).unwrap();
+        assert!(msg.text.contains("Issue created by"));
+        assert!(matches!(color(&msg), Some(slack::Color::NotPlanned)));
+        assert!(sections(&msg)[0].ends_with(">* `Closed as not planned`"));
+
+        let p = de("push", "push.json");
+        assert!(super::opening(&p).is_none());
+    }
 }
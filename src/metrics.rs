@@ -0,0 +1,33 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// Prometheus の text format で出す counter
+// name ごとに reason label で分ける
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<(&'static str, String), u64>>,
+}
+
+impl Metrics {
+    pub fn inc(&self, name: &'static str, reason: &str) -> u64 {
+        let mut counters = self.counters.lock().unwrap();
+        let c = counters.entry((name, reason.to_string())).or_default();
+        *c += 1;
+        *c
+    }
+
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+
+        let mut out = String::new();
+        let mut last = None;
+        for ((name, reason), count) in counters.iter() {
+            if last != Some(name) {
+                out += &format!("# TYPE {name} counter\n");
+                last = Some(name);
+            }
+            out += &format!("{name}{{reason=\"{reason}\"}} {count}\n");
+        }
+        out
+    }
+}
@@ -0,0 +1,375 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::channel::mpsc;
+use futures::StreamExt;
+use tracing::{debug, error};
+
+use crate::delivery::Guard;
+use crate::metrics::Metrics;
+use crate::slack;
+use crate::thread::{Parent, Thread, Threads};
+
+// 1 delivery 分の投稿
+#[derive(Debug)]
+pub struct Job {
+    // 編集など, 親を書き換えるだけで投稿しない event は None
+    pub message: Option<slack::Message>,
+    pub targets: Vec<(String, String)>, // (channel, display_name)
+    pub event: String,
+    pub delivery: String,
+    pub thread: Option<Thread>,
+    // Thread::Update で親を書き換える内容
+    pub opening: Option<slack::Message>,
+    // 全ての channel に投稿できたら処理済みにする. 失敗したら drop して再送を受け付ける
+    pub claim: Option<Guard>,
+}
+
+impl Job {
+    async fn post(mut self, client: &slack::Client, threads: &Threads, metrics: &Metrics) {
+        let claim = self.claim.take();
+        let mut failed = false;
+        debug!(
+            "posting {} to {} channels. delivery = {}",
+            self.event,
+            self.targets.len(),
+            self.delivery
+        );
+        for (channel, display_name) in &self.targets {
+            // 親が無ければ (hubhook を入れる前の issue など) そのまま投稿する
+            let parent = match &self.thread {
+                Some(Thread::Reply { node_id, .. } | Thread::Update { node_id }) => {
+                    threads.get(channel, node_id)
+                }
+                _ => None,
+            };
+
+            // channel ID が分からない古い親は書き換えられないので投稿する
+            if let (
+                Some(Parent {
+                    channel_id: Some(channel_id),
+                    ts,
+                }),
+                Some(opening),
+            ) = (&parent, &self.opening)
+            {
+                let update = opening.clone().into_update(channel_id, ts);
+                match update.post(client).await {
+                    Ok(_) => {
+                        debug!("updated {ts} in {channel}");
+                        continue;
+                    }
+                    // 親が消された場合などは新しく投稿する
+                    Err(e) => {
+                        self.report(channel, &e, metrics);
+                        failed |= self.message.is_none();
+                    }
+                }
+            }
+
+            let Some(message) = &self.message else {
+                continue;
+            };
+            let mut payload = message.clone().into_payload(channel, Some(display_name));
+            if let (Some(Thread::Reply { broadcast, .. }), Some(parent)) = (&self.thread, &parent) {
+                payload.thread_ts = Some(parent.ts.clone());
+                payload.reply_broadcast = *broadcast;
+            }
+
+            match payload.post(client).await {
+                Ok(posted) => {
+                    debug!("posted to {channel}: ts = {}", posted.ts);
+                    if let Some(Thread::Start { node_id }) = &self.thread {
+                        threads.insert(channel, node_id, &posted);
+                    }
+                }
+                Err(e) => {
+                    self.report(channel, &e, metrics);
+                    failed = true;
+                }
+            }
+        }
+
+        if let (Some(claim), false) = (claim, failed) {
+            claim.done();
+        }
+    }
+
+    fn report(&self, channel: &str, e: &slack::Error, metrics: &Metrics) {
+        error!(
+            "could not post {} to {channel}: {e}. delivery = {}",
+            self.event, self.delivery
+        );
+        metrics.inc("hubhook_slack_post_failures_total", e.reason());
+        sentry::with_scope(
+            |scope| {
+                scope.set_tag("event", &self.event);
+                scope.set_tag("delivery", &self.delivery);
+                scope.set_tag("channel", channel);
+                scope.set_tag("slack_error", e.reason());
+            },
+            || sentry::capture_error(e),
+        );
+    }
+}
+
+// GitHub を待たせないように Slack への投稿は background で行う
+#[derive(Debug, Clone)]
+pub struct Queue {
+    tx: mpsc::UnboundedSender<Job>,
+    len: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl Queue {
+    pub fn new(capacity: usize) -> (Self, mpsc::UnboundedReceiver<Job>) {
+        let (tx, rx) = mpsc::unbounded();
+        let queue = Queue {
+            tx,
+            len: Arc::default(),
+            capacity,
+        };
+        (queue, rx)
+    }
+
+    // 全ての Queue が drop されると, 残っている job を投稿し終えてから終わる
+    pub fn start(
+        client: slack::Client,
+        threads: Arc<Threads>,
+        capacity: usize,
+        concurrency: usize,
+        metrics: Arc<Metrics>,
+    ) -> (Self, actix_web::rt::task::JoinHandle<()>) {
+        let (queue, rx) = Queue::new(capacity);
+        let len = queue.len.clone();
+
+        let worker = actix_web::rt::spawn(async move {
+            rx.for_each_concurrent(concurrency, |job| {
+                len.fetch_sub(1, Ordering::SeqCst);
+                job.post(&client, &threads, &metrics)
+            })
+            .await;
+        });
+
+        (queue, worker)
+    }
+
+    // 溢れた場合は job を返す
+    pub fn push(&self, job: Job) -> Result<(), Box<Job>> {
+        if self.len.fetch_add(1, Ordering::SeqCst) >= self.capacity {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+            return Err(Box::new(job));
+        }
+        self.tx.unbounded_send(job).map_err(|e| {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+            Box::new(e.into_inner())
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{client, message, mock_server};
+
+    fn job(delivery: &str) -> Job {
+        Job {
+            message: Some(message("test")),
+            targets: vec![("ch".to_string(), "hubhook".to_string())],
+            event: "issues".to_string(),
+            delivery: delivery.to_string(),
+            thread: None,
+            opening: None,
+            claim: None,
+        }
+    }
+
+    #[test]
+    fn bounded() {
+        let (queue, mut rx) = Queue::new(2);
+        assert!(queue.push(job("1")).is_ok());
+        assert!(queue.clone().push(job("2")).is_ok());
+        let rejected = queue.push(job("3")).unwrap_err();
+        assert_eq!(rejected.delivery, "3");
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(rx.try_next().unwrap().unwrap().delivery, "1");
+        // worker が取り出した時に減らす
+        queue.len.fetch_sub(1, Ordering::SeqCst);
+        assert!(queue.push(job("4")).is_ok());
+
+        // worker が止まっていたら受け付けない
+        drop(rx);
+        assert!(queue.push(job("5")).is_err());
+        assert_eq!(queue.len(), 2);
+    }
+
+    // 受け取った method と body を記録する mock. channel "ng" には投稿できない
+    async fn mock_slack(
+        calls: actix_web::web::Data<std::sync::Mutex<Vec<(String, serde_json::Value)>>>,
+        req: actix_web::HttpRequest,
+        body: actix_web::web::Json<serde_json::Value>,
+    ) -> actix_web::HttpResponse {
+        let mut calls = calls.lock().unwrap();
+        let ng = body["channel"] == "ng";
+        calls.push((req.path().to_string(), body.into_inner()));
+        if ng {
+            return actix_web::HttpResponse::Ok()
+                .json(serde_json::json!({ "ok": false, "error": "channel_not_found" }));
+        }
+        actix_web::HttpResponse::Ok().json(serde_json::json!({
+            "ok": true,
+            "channel": "C1",
+            "ts": format!("{}.0", calls.len()),
+        }))
+    }
+
+    #[actix_web::test]
+    async fn thread() {
+        use actix_web::web;
+
+        let calls = web::Data::new(std::sync::Mutex::new(
+            Vec::<(String, serde_json::Value)>::new(),
+        ));
+        let (url, handle) = {
+            let calls = calls.clone();
+            mock_server(move |cfg| {
+                cfg.app_data(calls.clone())
+                    .route("/chat.postMessage", web::post().to(mock_slack))
+                    .route("/chat.update", web::post().to(mock_slack));
+            })
+        };
+
+        let client = client(&url, std::time::Duration::from_secs(5));
+        // 並列に投稿すると順番が変わるので 1 つずつ
+        let (queue, worker) = Queue::start(
+            client,
+            Arc::new(Threads::memory(std::time::Duration::from_secs(60), 100)),
+            10,
+            1,
+            Arc::new(Metrics::default()),
+        );
+
+        let reply = |node_id: &str, broadcast| Thread::Reply {
+            node_id: node_id.to_string(),
+            broadcast,
+        };
+        let update = |node_id: &str| Thread::Update {
+            node_id: node_id.to_string(),
+        };
+        let opening = || Some(message("opening"));
+        let jobs = [
+            // 親がまだ無いので thread にしない
+            (Some(reply("PR_1", false)), true, None),
+            (
+                Some(Thread::Start {
+                    node_id: "PR_1".to_string(),
+                }),
+                true,
+                None,
+            ),
+            (Some(reply("PR_1", true)), true, None),
+            // 親を書き換えるだけで投稿しない
+            (Some(update("PR_1")), true, opening()),
+            (Some(update("PR_1")), false, opening()),
+            // 親が無ければ投稿する. 投稿するものが無ければ何もしない
+            (Some(update("PR_2")), true, opening()),
+            (Some(update("PR_2")), false, opening()),
+            (None, true, None),
+        ];
+        for (i, (thread, post, opening)) in jobs.into_iter().enumerate() {
+            let mut job = job(&i.to_string());
+            if !post {
+                job.message = None;
+            }
+            job.thread = thread;
+            job.opening = opening;
+            queue.push(job).unwrap();
+        }
+        drop(queue);
+        worker.await.unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        let calls: Vec<_> = calls
+            .iter()
+            .map(|(method, b)| match method.as_str() {
+                "/chat.update" => (
+                    "update",
+                    b["ts"].as_str(),
+                    b["text"].as_str() == Some("opening"),
+                ),
+                _ => (
+                    "post",
+                    b["thread_ts"].as_str(),
+                    b["reply_broadcast"].as_bool() == Some(true),
+                ),
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                ("post", None, false),
+                ("post", None, false),
+                ("post", Some("2.0"), true),
+                ("update", Some("2.0"), true),
+                ("update", Some("2.0"), true),
+                ("post", None, false),
+                ("post", None, false),
+            ]
+        );
+
+        handle.stop(false).await;
+    }
+
+    #[actix_web::test]
+    async fn claim() {
+        use actix_web::web;
+
+        use crate::delivery::{Claim, Deliveries};
+
+        let calls = web::Data::new(std::sync::Mutex::new(
+            Vec::<(String, serde_json::Value)>::new(),
+        ));
+        let (url, handle) = {
+            let calls = calls.clone();
+            mock_server(move |cfg| {
+                cfg.app_data(calls.clone())
+                    .route("/chat.postMessage", web::post().to(mock_slack));
+            })
+        };
+        let (queue, worker) = Queue::start(
+            client(&url, std::time::Duration::from_secs(5)),
+            Arc::new(Threads::memory(std::time::Duration::from_secs(60), 100)),
+            10,
+            1,
+            Arc::new(Metrics::default()),
+        );
+
+        let deliveries = Arc::new(Deliveries::memory(std::time::Duration::from_secs(60), 100));
+        for (delivery, channels) in [("ok", &["a", "b"][..]), ("failed", &["a", "ng"][..])] {
+            let Claim::New(guard) = deliveries.claim(delivery) else {
+                panic!("{delivery} is new");
+            };
+            let mut job = job(delivery);
+            job.targets = channels
+                .iter()
+                .map(|c| (c.to_string(), "hubhook".to_string()))
+                .collect();
+            job.claim = Some(guard);
+            queue.push(job).unwrap();
+        }
+        drop(queue);
+        worker.await.unwrap();
+
+        // 全ての channel に投稿できたものだけ処理済みになる
+        assert!(matches!(deliveries.claim("ok"), Claim::Duplicate));
+        assert!(matches!(deliveries.claim("failed"), Claim::New(_)));
+
+        handle.stop(false).await;
+    }
+}
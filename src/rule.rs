@@ -0,0 +1,601 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use regex::{RegexBuilder, RegexSet, RegexSetBuilder};
+use serde::Deserialize;
+
+use crate::github;
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct RuleMatchResult {
+    pub display_name: String,
+    pub channel: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub channel: String,
+    pub query: Query,
+    pub exclude_query: Option<Query>,
+    pub display_name: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Query {
+    repo: Option<String>,
+    topic: Option<String>,
+    user: Option<String>,
+    //event: Option<String>,
+    title: Option<String>,
+    body: Option<String>,
+    label: Option<String>,
+    review_state: Option<String>,
+    branch: Option<String>,
+    tag: Option<String>,
+    workflow: Option<String>,
+    conclusion: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Repo,
+    Topic,
+    User,
+    Title,
+    Body,
+    Label,
+    ReviewState,
+    Branch,
+    Tag,
+    Workflow,
+    Conclusion,
+}
+
+const FIELDS: [Field; 11] = [
+    Field::Repo,
+    Field::Topic,
+    Field::User,
+    Field::Title,
+    Field::Body,
+    Field::Label,
+    Field::ReviewState,
+    Field::Branch,
+    Field::Tag,
+    Field::Workflow,
+    Field::Conclusion,
+];
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Repo => "repo",
+            Field::Topic => "topic",
+            Field::User => "user",
+            Field::Title => "title",
+            Field::Body => "body",
+            Field::Label => "label",
+            Field::ReviewState => "review_state",
+            Field::Branch => "branch",
+            Field::Tag => "tag",
+            Field::Workflow => "workflow",
+            Field::Conclusion => "conclusion",
+        }
+    }
+
+    fn pattern(self, query: &Query) -> Option<&String> {
+        match self {
+            Field::Repo => query.repo.as_ref(),
+            Field::Topic => query.topic.as_ref(),
+            Field::User => query.user.as_ref(),
+            Field::Title => query.title.as_ref(),
+            Field::Body => query.body.as_ref(),
+            Field::Label => query.label.as_ref(),
+            Field::ReviewState => query.review_state.as_ref(),
+            Field::Branch => query.branch.as_ref(),
+            Field::Tag => query.tag.as_ref(),
+            Field::Workflow => query.workflow.as_ref(),
+            Field::Conclusion => query.conclusion.as_ref(),
+        }
+    }
+
+    // 複数ある値 (topic, label など) はどれか 1 つに一致すれば良い
+    fn values(self, payload: &github::Payload) -> Vec<Cow<'_, str>> {
+        match self {
+            Field::Repo => vec![Cow::from(&payload.repo().full_name)],
+            Field::Topic => payload.repo().topics.iter().map(Cow::from).collect(),
+            Field::User => vec![Cow::from(&payload.sender().login)],
+            Field::Title => vec![Cow::from(payload.title())],
+            Field::Body => vec![Cow::from(payload.body())],
+            Field::Label => payload
+                .labels()
+                .iter()
+                .map(|l| Cow::from(l.to_string()))
+                .collect(),
+            Field::ReviewState => payload
+                .review_state()
+                .map(|s| Cow::from(s.to_string()))
+                .into_iter()
+                .collect(),
+            Field::Branch => payload.branch().map(Cow::from).into_iter().collect(),
+            Field::Tag => payload.tag().map(Cow::from).into_iter().collect(),
+            Field::Workflow => payload.workflow().map(Cow::from).into_iter().collect(),
+            Field::Conclusion => payload
+                .conclusion()
+                .map(|c| Cow::from(c.to_string()))
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // rule 単体で不正な pattern
+    InvalidPattern {
+        index: usize,
+        location: String, // e.g. query.title
+        pattern: String,
+        reason: String,
+    },
+    // 個々の pattern は正しいが RegexSet にまとめられなかった
+    Build {
+        location: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidPattern {
+                index,
+                location,
+                pattern,
+                reason,
+            } => write!(f, "rule[{index}].{location} = {pattern:?}: {reason}"),
+            Error::Build { location, reason } => {
+                write!(f, "could not compile {location} of all rules: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// 動くが恐らく意図通りでない rule
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lint {
+    // query が空なので全ての event に一致する
+    EmptyQuery { index: usize },
+    // exclude_query が空なので何も除外しない
+    EmptyExcludeQuery { index: usize },
+    // 空の pattern は何にも一致しない. query なら rule が無効になり, exclude_query なら何も除外しない
+    EmptyPattern { index: usize, location: String },
+    // 前の rule と channel, query, exclude_query が同じ
+    Duplicate { index: usize, of: usize },
+    // query と exclude_query に同じ pattern があるので一致しない
+    Unreachable { index: usize, field: &'static str },
+}
+
+// simulate 用に rule の各 field がどう判定されたか
+#[derive(Debug, Default)]
+pub struct Explanation {
+    pub query: Vec<FieldResult>,
+    pub exclude_query: Vec<FieldResult>,
+    pub matched: bool,
+}
+
+#[derive(Debug)]
+pub struct FieldResult {
+    pub field: &'static str,
+    pub pattern: String,
+    pub values: Vec<String>, // payload 側の値
+    pub hit: bool,
+}
+
+impl Query {
+    fn is_empty(&self) -> bool {
+        FIELDS.iter().all(|f| f.pattern(self).is_none())
+    }
+}
+
+impl Rule {
+    fn queries(&self) -> impl Iterator<Item = (&'static str, &Query)> {
+        std::iter::once(("query", &self.query))
+            .chain(self.exclude_query.iter().map(|q| ("exclude_query", q)))
+    }
+
+    // 全ての query が regex として compile できるか
+    fn validate(&self, index: usize) -> Result<(), Error> {
+        for (kind, query) in self.queries() {
+            for field in FIELDS {
+                let Some(pattern) = field.pattern(query) else {
+                    continue;
+                };
+                let invalid = |reason: String| Error::InvalidPattern {
+                    index,
+                    location: format!("{kind}.{}", field.name()),
+                    pattern: pattern.clone(),
+                    reason,
+                };
+
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| invalid(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// field ごとに全 rule の pattern をまとめた RegexSet
+#[derive(Debug, Clone)]
+struct FieldSet {
+    field: Field,
+    set: RegexSet,
+    rules: Vec<usize>, // set の i 番目の pattern を持つ rule の index
+}
+
+impl FieldSet {
+    fn build<'a>(
+        kind: &str,
+        rules: &'a [Rule],
+        query: impl Fn(&'a Rule) -> Option<&'a Query>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut sets = vec![];
+        for field in FIELDS {
+            let (rules, patterns): (Vec<usize>, Vec<&String>) = rules
+                .iter()
+                .enumerate()
+                .filter_map(|(i, r)| Some((i, field.pattern(query(r)?)?)))
+                .unzip();
+            if patterns.is_empty() {
+                continue;
+            }
+
+            let set = RegexSetBuilder::new(patterns)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| Error::Build {
+                    location: format!("{kind}.{}", field.name()),
+                    reason: e.to_string(),
+                })?;
+            sets.push(FieldSet { field, set, rules });
+        }
+        Ok(sets)
+    }
+
+    // (rule の index, 一致したか)
+    // 空の pattern は regex としては何にでも一致するが, 以前から何にも一致しない扱いにしている
+    fn hits(&self, payload: &github::Payload) -> impl Iterator<Item = (usize, bool)> + '_ {
+        let mut hit = vec![false; self.set.len()];
+        for value in self.field.values(payload) {
+            for i in self.set.matches(&value).iter() {
+                hit[i] = !self.set.patterns()[i].is_empty();
+            }
+        }
+        self.rules.iter().copied().zip(hit)
+    }
+}
+
+// config の読み込み時に compile しておく
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    include: Vec<FieldSet>,
+    exclude: Vec<FieldSet>,
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<Rule>) -> Result<Self, Error> {
+        // どの rule が悪いか分かるように先に 1 つずつ compile する
+        for (index, rule) in rules.iter().enumerate() {
+            rule.validate(index)?;
+        }
+
+        let include = FieldSet::build("query", &rules, |r| Some(&r.query))?;
+        let exclude = FieldSet::build("exclude_query", &rules, |r| r.exclude_query.as_ref())?;
+
+        Ok(RuleSet {
+            rules,
+            include,
+            exclude,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = vec![];
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.query.is_empty() {
+                lints.push(Lint::EmptyQuery { index });
+            }
+
+            for (kind, query) in rule.queries() {
+                for field in FIELDS {
+                    if field.pattern(query).is_some_and(|p| p.is_empty()) {
+                        lints.push(Lint::EmptyPattern {
+                            index,
+                            location: format!("{kind}.{}", field.name()),
+                        });
+                    }
+                }
+            }
+
+            if let Some(exclude) = &rule.exclude_query {
+                if exclude.is_empty() {
+                    lints.push(Lint::EmptyExcludeQuery { index });
+                }
+                for field in FIELDS {
+                    if field.pattern(&rule.query).is_some()
+                        && field.pattern(&rule.query) == field.pattern(exclude)
+                    {
+                        lints.push(Lint::Unreachable {
+                            index,
+                            field: field.name(),
+                        });
+                    }
+                }
+            }
+
+            let same = |r: &Rule| {
+                r.channel == rule.channel
+                    && r.query == rule.query
+                    && r.exclude_query == rule.exclude_query
+            };
+            if let Some(of) = self.rules[..index].iter().position(same) {
+                lints.push(Lint::Duplicate { index, of });
+            }
+        }
+
+        lints
+    }
+
+    // query の field が全て一致し, exclude_query の field がどれも一致しない rule
+    fn matched(&self, payload: &github::Payload) -> Vec<bool> {
+        let mut matched = vec![true; self.rules.len()];
+
+        for fs in &self.include {
+            for (i, hit) in fs.hits(payload) {
+                matched[i] &= hit;
+            }
+        }
+        for fs in &self.exclude {
+            for (i, hit) in fs.hits(payload) {
+                matched[i] &= !hit;
+            }
+        }
+
+        matched
+    }
+
+    // matched と同じ RegexSet を使って field ごとの結果を返す
+    pub fn explain(&self, payload: &github::Payload) -> Vec<Explanation> {
+        let mut explanations: Vec<Explanation> =
+            self.rules.iter().map(|_| Explanation::default()).collect();
+
+        let sets = self.include.iter().map(|fs| (fs, false));
+        let sets = sets.chain(self.exclude.iter().map(|fs| (fs, true)));
+        for (fs, exclude) in sets {
+            let values: Vec<String> = fs
+                .field
+                .values(payload)
+                .into_iter()
+                .map(|v| v.into_owned())
+                .collect();
+
+            for (i, hit) in fs.hits(payload) {
+                let rule = &self.rules[i];
+                let (query, results) = if exclude {
+                    (
+                        rule.exclude_query.as_ref(),
+                        &mut explanations[i].exclude_query,
+                    )
+                } else {
+                    (Some(&rule.query), &mut explanations[i].query)
+                };
+                // fs.rules には pattern を持つ rule しか無い
+                let pattern = query.and_then(|q| fs.field.pattern(q)).unwrap();
+                results.push(FieldResult {
+                    field: fs.field.name(),
+                    pattern: pattern.clone(),
+                    values: values.clone(),
+                    hit,
+                });
+            }
+        }
+
+        for (e, matched) in explanations.iter_mut().zip(self.matched(payload)) {
+            e.matched = matched;
+        }
+        explanations
+    }
+
+    pub fn matches(&self, payload: &github::Payload) -> HashMap<String, RuleMatchResult> {
+        let mut v = HashMap::<String, RuleMatchResult>::new();
+
+        for (r, matched) in self.rules.iter().zip(self.matched(payload)) {
+            // not match
+            if !matched {
+                continue;
+            }
+
+            // multiple display_name
+            let mut display_name = r.display_name.clone();
+            if let Some(res) = v.get(&r.channel) {
+                display_name = res.display_name.to_string() + "&" + &display_name;
+            }
+
+            let res = RuleMatchResult {
+                display_name,
+                channel: r.channel.clone(),
+            };
+            v.insert(r.channel.clone(), res);
+        }
+
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::de;
+
+    fn rules(json: &str) -> Result<RuleSet, Error> {
+        RuleSet::new(serde_json::from_str(json).unwrap())
+    }
+
+    fn channels(rules: &RuleSet, payload: &github::Payload) -> Vec<(String, String)> {
+        let mut v: Vec<_> = rules
+            .matches(payload)
+            .into_values()
+            .map(|m| (m.channel, m.display_name))
+            .collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn match_query() {
+        let rules = rules(
+            r#"[
+                { "channel": "all", "display_name": "all", "query": {} },
+                { "channel": "repo", "display_name": "repo", "query": { "repo": "^ARKEDGE/hubhook$" } },
+                { "channel": "repo", "display_name": "label", "query": { "repo": "hubhook", "label": "bug|enhancement" } },
+                { "channel": "topic", "display_name": "topic", "query": { "topic": "^github$" } },
+                { "channel": "other", "display_name": "other", "query": { "repo": "^arkedge/other$" } },
+                { "channel": "nolabel", "display_name": "nolabel", "query": { "label": "wontfix" } },
+                { "channel": "branch", "display_name": "branch", "query": { "branch": "^main$" } },
+                { "channel": "exclude", "display_name": "exclude", "query": { "repo": "hubhook" }, "exclude_query": { "user": "^sksat$", "title": "never" } }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 8);
+
+        let issue = de("issues", "issues_labeled.json");
+        assert_eq!(
+            channels(&rules, &issue),
+            [("all", "all"), ("repo", "repo&label"), ("topic", "topic"),]
+                .map(|(c, d)| (c.to_string(), d.to_string()))
+        );
+
+        // label の無い push には label の query は一致しない
+        let push = de("push", "push.json");
+        let explanations = rules.explain(&push);
+        assert_eq!(explanations.len(), 8);
+        let label = &explanations[2];
+        assert!(!label.matched);
+        assert_eq!(
+            label
+                .query
+                .iter()
+                .map(|r| (r.field, r.hit))
+                .collect::<Vec<_>>(),
+            [("repo", true), ("label", false)]
+        );
+        let exclude = &explanations[7];
+        assert!(!exclude.matched);
+        assert_eq!(exclude.exclude_query[0].field, "user");
+        assert_eq!(exclude.exclude_query[0].values, ["sksat"]);
+        assert!(exclude.exclude_query[0].hit);
+
+        assert_eq!(
+            channels(&rules, &push),
+            [
+                ("all", "all"),
+                ("branch", "branch"),
+                ("repo", "repo"),
+                ("topic", "topic"),
+            ]
+            .map(|(c, d)| (c.to_string(), d.to_string()))
+        );
+    }
+
+    #[test]
+    fn empty_pattern() {
+        let rules = rules(
+            r#"[
+                { "channel": "query", "display_name": "query", "query": { "repo": "hubhook", "title": "" } },
+                { "channel": "exclude", "display_name": "exclude", "query": { "repo": "hubhook" }, "exclude_query": { "title": "" } }
+            ]"#,
+        )
+        .unwrap();
+
+        // 空の pattern は何にも一致しないので, query では一致せず exclude_query では除外しない
+        let issue = de("issues", "issues_labeled.json");
+        assert_eq!(
+            channels(&rules, &issue),
+            [("exclude".to_string(), "exclude".to_string())]
+        );
+        let explanations = rules.explain(&issue);
+        assert!(!explanations[0].query[1].hit);
+        assert!(!explanations[1].exclude_query[0].hit);
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let e = rules(
+            r#"[
+                { "channel": "ch", "display_name": "ok", "query": { "repo": "hubhook" } },
+                { "channel": "ch", "display_name": "ng", "query": { "repo": "hubhook" }, "exclude_query": { "title": "(wip" } }
+            ]"#,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(
+                &e,
+                Error::InvalidPattern { index: 1, location, pattern, .. }
+                    if location == "exclude_query.title" && pattern == "(wip"
+            ),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn lint() {
+        let rules = rules(
+            r#"[
+                { "channel": "a", "display_name": "ok", "query": { "repo": "hubhook" }, "exclude_query": { "label": "wontfix" } },
+                { "channel": "a", "display_name": "all", "query": {} },
+                { "channel": "a", "display_name": "noop", "query": { "repo": "hubhook" }, "exclude_query": {} },
+                { "channel": "a", "display_name": "dup", "query": { "repo": "hubhook" }, "exclude_query": { "label": "wontfix" } },
+                { "channel": "b", "display_name": "other channel", "query": { "repo": "hubhook" }, "exclude_query": { "label": "wontfix" } },
+                { "channel": "b", "display_name": "never", "query": { "repo": "hubhook", "user": "bot" }, "exclude_query": { "user": "bot" } },
+                { "channel": "b", "display_name": "empty", "query": { "repo": "hubhook", "label": "" }, "exclude_query": { "title": "" } }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.lint(),
+            [
+                Lint::EmptyQuery { index: 1 },
+                Lint::EmptyExcludeQuery { index: 2 },
+                Lint::Duplicate { index: 3, of: 0 },
+                Lint::Unreachable {
+                    index: 5,
+                    field: "user"
+                },
+                Lint::EmptyPattern {
+                    index: 6,
+                    location: "query.label".to_string()
+                },
+                Lint::EmptyPattern {
+                    index: 6,
+                    location: "exclude_query.title".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::str::FromStr;
+
+use actix_web::http::header::HeaderMap;
+
+use crypto_hashes::sha1::Sha1;
+use crypto_hashes::sha2::Sha256;
+use hmac::{Hmac, Mac};
+
+use crate::Rejection;
+
+type HmacSha1 = Hmac<Sha1>;
+type HmacSha256 = Hmac<Sha256>;
+
+// どの署名ヘッダで検証するか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    // X-Hub-Signature-256 のみ
+    Sha256,
+    // X-Hub-Signature-256 が無ければ legacy な X-Hub-Signature (SHA-1) を使う
+    Sha256OrSha1,
+    // X-Hub-Signature (SHA-1) のみ
+    Sha1,
+}
+
+impl FromStr for SignaturePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(SignaturePolicy::Sha256),
+            "sha256-or-sha1" => Ok(SignaturePolicy::Sha256OrSha1),
+            "sha1" => Ok(SignaturePolicy::Sha1),
+            _ => Err(format!(
+                "unknown signature policy: {s} (expected sha256, sha256-or-sha1 or sha1)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Signature {
+    Sha256(Vec<u8>),
+    Sha1(Vec<u8>),
+}
+
+impl Signature {
+    pub fn from_headers(headers: &HeaderMap, policy: SignaturePolicy) -> Result<Self, Rejection> {
+        let sha256 = headers.get("x-hub-signature-256");
+        let sha1 = headers.get("x-hub-signature");
+
+        match policy {
+            SignaturePolicy::Sha256 => Ok(Signature::Sha256(parse(sha256, "sha256=")?)),
+            SignaturePolicy::Sha256OrSha1 if sha256.is_some() => {
+                Ok(Signature::Sha256(parse(sha256, "sha256=")?))
+            }
+            SignaturePolicy::Sha256OrSha1 | SignaturePolicy::Sha1 => {
+                Ok(Signature::Sha1(parse(sha1, "sha1=")?))
+            }
+        }
+    }
+
+    // 一致した secret の index を返す
+    pub fn verify<S: AsRef<[u8]>>(&self, secrets: &[S], body: &[u8]) -> Option<usize> {
+        secrets
+            .iter()
+            .position(|secret| self.verify_one(secret.as_ref(), body))
+    }
+
+    // verify_slice は constant-time で比較する
+    fn verify_one(&self, secret: &[u8], body: &[u8]) -> bool {
+        match self {
+            Signature::Sha256(sig) => {
+                let mut mac = HmacSha256::new_from_slice(secret).unwrap();
+                mac.update(body);
+                mac.verify_slice(sig).is_ok()
+            }
+            Signature::Sha1(sig) => {
+                let mut mac = HmacSha1::new_from_slice(secret).unwrap();
+                mac.update(body);
+                mac.verify_slice(sig).is_ok()
+            }
+        }
+    }
+}
+
+fn parse(
+    header: Option<&actix_web::http::header::HeaderValue>,
+    prefix: &str,
+) -> Result<Vec<u8>, Rejection> {
+    let header = header.ok_or(Rejection::MissingSignature)?;
+    let sig = header
+        .to_str()
+        .map_err(|_| Rejection::MalformedSignature)?
+        .strip_prefix(prefix)
+        .ok_or(Rejection::MalformedSignature)?;
+    hex::decode(sig).map_err(|_| Rejection::MalformedSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    const SECRET: &[u8] = b"It's a Secret to Everybody";
+    const BODY: &[u8] = b"Hello, World!";
+    // https://docs.github.com/en/webhooks/using-webhooks/validating-webhook-deliveries#testing-the-webhook-payload-validation
+    const SHA256: &str = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    fn headers(h: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (k, v) in h {
+            headers.insert(
+                HeaderName::from_static(k),
+                HeaderValue::from_str(v).unwrap(),
+            );
+        }
+        headers
+    }
+
+    fn sha1_header() -> String {
+        let mut mac = HmacSha1::new_from_slice(SECRET).unwrap();
+        mac.update(BODY);
+        format!("sha1={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn verify_sha256() {
+        let h = headers(&[("x-hub-signature-256", SHA256)]);
+        let sig = Signature::from_headers(&h, SignaturePolicy::Sha256).unwrap();
+        assert_eq!(sig.verify(&[SECRET], BODY), Some(0));
+        assert_eq!(sig.verify(&[b"wrong secret"], BODY), None);
+
+        // rotation 中は古い secret でも受け付ける
+        let secrets: [&[u8]; 3] = [b"new secret", SECRET, b"other"];
+        assert_eq!(sig.verify(&secrets, BODY), Some(1));
+        assert_eq!(sig.verify(&secrets[..1], BODY), None);
+
+        // 長さが違う署名でも panic しない
+        let h = headers(&[("x-hub-signature-256", "sha256=7571")]);
+        let sig = Signature::from_headers(&h, SignaturePolicy::Sha256).unwrap();
+        assert_eq!(sig.verify(&[SECRET], BODY), None);
+    }
+
+    #[test]
+    fn policy() {
+        let sha1 = sha1_header();
+        let only_sha1 = headers(&[("x-hub-signature", &sha1)]);
+        let both = headers(&[("x-hub-signature-256", SHA256), ("x-hub-signature", &sha1)]);
+
+        assert_eq!(
+            Signature::from_headers(&only_sha1, SignaturePolicy::Sha256),
+            Err(Rejection::MissingSignature)
+        );
+
+        let sig = Signature::from_headers(&only_sha1, SignaturePolicy::Sha256OrSha1).unwrap();
+        assert!(matches!(sig, Signature::Sha1(_)));
+        assert_eq!(sig.verify(&[SECRET], BODY), Some(0));
+
+        let sig = Signature::from_headers(&both, SignaturePolicy::Sha256OrSha1).unwrap();
+        assert!(matches!(sig, Signature::Sha256(_)));
+
+        let sig = Signature::from_headers(&both, SignaturePolicy::Sha1).unwrap();
+        assert!(matches!(sig, Signature::Sha1(_)));
+    }
+
+    #[test]
+    fn malformed() {
+        for sig in ["757107ea", "sha256=not-hex", "sha1=757107ea"] {
+            let h = headers(&[("x-hub-signature-256", sig)]);
+            assert_eq!(
+                Signature::from_headers(&h, SignaturePolicy::Sha256),
+                Err(Rejection::MalformedSignature)
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use crate::config::Config;
+use crate::rule::FieldResult;
+use crate::thread::{Parent, Thread, Threads};
+use crate::{github, message, slack};
+
+// `hubhook simulate`: 保存した payload にどの rule が一致するかを表示する. Slack には送らない
+// thread_db を渡すと serve と同じ store を見て post / reply / update のどれになるかを決める
+pub fn run(config_path: &Path, event: &str, payload_path: &Path, thread_db: Option<&Path>) -> bool {
+    let config = match Config::load(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return false;
+        }
+    };
+
+    let body = match std::fs::read(payload_path) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("error: could not read {}: {e}", payload_path.display());
+            return false;
+        }
+    };
+    // serve が使っている DB なので読み取り専用で開く
+    let threads = match thread_db {
+        Some(path) => match Threads::sqlite_read_only(path) {
+            Ok(threads) => Some(threads),
+            Err(e) => {
+                eprintln!("error: could not open {}: {e}", path.display());
+                return false;
+            }
+        },
+        None => None,
+    };
+
+    let payload = match github::Payload::from_event(event, &body) {
+        Ok(Some(payload)) => payload,
+        Ok(None) => {
+            eprintln!("error: unsupported event: {event}");
+            return false;
+        }
+        Err(e) => {
+            eprintln!("error: could not deserialize {event} payload: {e}");
+            return false;
+        }
+    };
+
+    println!("# rules");
+    for (index, e) in config.rules.explain(&payload).iter().enumerate() {
+        let result = if e.matched { "match" } else { "no match" };
+        println!("[{result}] {}", config.rule_name(index));
+        for r in &e.query {
+            let verdict = if r.hit { "matched" } else { "NOT matched" };
+            println!("    query.{}: {verdict}", field(r));
+        }
+        for r in &e.exclude_query {
+            let verdict = if r.hit {
+                "matched -> excluded"
+            } else {
+                "not matched"
+            };
+            println!("    exclude_query.{}: {verdict}", field(r));
+        }
+    }
+
+    let matches = config.rules.matches(&payload);
+    println!();
+    println!("# messages");
+    if matches.is_empty() {
+        println!("no rule matched, nothing would be posted");
+        return true;
+    }
+
+    // serve の render と同じく, 状態の変化は最初の message の書き換えになる
+    let thread = Thread::of(&payload);
+    let opening = match thread {
+        Some(Thread::Update { .. }) => message::opening(&payload),
+        _ => None,
+    };
+
+    let msg: Result<slack::Message, message::Error> = (&payload).try_into();
+    let msg = match msg {
+        Ok(msg) => Some(msg),
+        Err(message::Error::Unsupported(reason)) if opening.is_some() => {
+            println!("no new message: {reason}");
+            None
+        }
+        Err(message::Error::Unsupported(reason)) => {
+            println!("nothing would be posted: {reason}");
+            return true;
+        }
+        Err(e) => {
+            eprintln!("error: could not render the message: {e}");
+            return false;
+        }
+    };
+
+    let mut matches: Vec<_> = matches.into_values().collect();
+    matches.sort_by(|a, b| a.channel.cmp(&b.channel));
+    for m in matches {
+        println!("## {}", m.channel);
+
+        // Some(None) は store に親が無い. None は store を渡されていないので分からない
+        let parent: Option<Option<Parent>> = match (&thread, &threads) {
+            (Some(Thread::Reply { node_id, .. } | Thread::Update { node_id }), Some(threads)) => {
+                Some(threads.get(&m.channel, node_id))
+            }
+            (Some(Thread::Reply { .. } | Thread::Update { .. }), None) => None,
+            _ => Some(None),
+        };
+
+        // queue の Job::post と同じ順に判断する
+        if let Some(opening) = &opening {
+            match &parent {
+                Some(Some(Parent {
+                    channel_id: Some(channel_id),
+                    ts,
+                })) => {
+                    println!("action: update the opening message");
+                    print(&opening.clone().into_update(channel_id, ts));
+                    continue;
+                }
+                None => {
+                    println!("action: update the opening message if hubhook posted it (pass --thread-db to check)");
+                    print(&opening.clone().into_update("<channel id>", "<ts>"));
+                }
+                // 親が無いか, channel ID の分からない古い親
+                Some(_) => {}
+            }
+        }
+
+        let Some(msg) = &msg else {
+            println!("action: none (no opening message to update)");
+            continue;
+        };
+        let mut payload = msg.clone().into_payload(&m.channel, Some(&m.display_name));
+        match (&thread, &parent) {
+            (Some(Thread::Reply { broadcast, .. }), Some(Some(parent))) => {
+                println!("action: reply in the thread");
+                payload.thread_ts = Some(parent.ts.clone());
+                payload.reply_broadcast = *broadcast;
+            }
+            (Some(Thread::Reply { broadcast, .. }), None) => {
+                println!("action: reply in the thread if hubhook posted the opening message, otherwise post without thread_ts (pass --thread-db to check)");
+                payload.thread_ts = Some("<ts>".to_string());
+                payload.reply_broadcast = *broadcast;
+            }
+            (Some(Thread::Update { .. }), None) => println!("action: otherwise post"),
+            (Some(Thread::Start { .. }), _) => println!("action: post and start a thread"),
+            _ => println!("action: post"),
+        }
+        print(&payload);
+    }
+
+    true
+}
+
+fn print<T: serde::Serialize>(payload: &T) {
+    println!("{}", serde_json::to_string_pretty(payload).unwrap());
+}
+
+// body などは長いので切り詰める
+const VALUE_MAX_CHARS: usize = 60;
+
+fn field(r: &FieldResult) -> String {
+    let values: Vec<String> = r
+        .values
+        .iter()
+        .map(|v| {
+            if v.chars().count() > VALUE_MAX_CHARS {
+                v.chars().take(VALUE_MAX_CHARS).collect::<String>() + "..."
+            } else {
+                v.clone()
+            }
+        })
+        .collect();
+    format!("{} = {:?} against {:?}", r.field, r.pattern, values)
+}
@@ -1,8 +1,57 @@
-use serde::Serialize;
+use std::time::Duration;
 
-use tracing::{debug, error};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use tracing::{debug, warn};
+
+// 1 回目を含めた試行回数
+const MAX_ATTEMPTS: u32 = 4;
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+// 接続を使い回すため 1 つだけ作って共有する
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: url::Url, // e.g. https://slack.com/api/
+    token: String,
+}
+
+impl Client {
+    pub fn new(
+        token: String,
+        base_url: &str,
+        timeout: Duration,
+        connect_timeout: Duration,
+    ) -> Result<Self, String> {
+        // join で最後の path segment が消えないように / で終わらせる
+        let base_url = if base_url.ends_with('/') {
+            base_url.to_string()
+        } else {
+            format!("{base_url}/")
+        };
+        let base_url =
+            url::Url::parse(&base_url).map_err(|e| format!("invalid Slack API URL: {e}"))?;
+
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(connect_timeout)
+            .build()
+            .map_err(|e| format!("could not build HTTP client: {e}"))?;
+
+        Ok(Client {
+            http,
+            base_url,
+            token,
+        })
+    }
+
+    fn url(&self, method: &str) -> url::Url {
+        self.base_url.join(method).unwrap()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Message {
     pub text: String,
     pub attachments: Option<Vec<Attachment>>,
@@ -15,18 +64,122 @@ pub struct MessagePayload {
     pub text: String,
     pub fallback: Option<String>,
     pub attachments: Option<Vec<Attachment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub reply_broadcast: bool,
 }
 
-#[derive(Debug, Serialize)]
+// Block Kit の blocks を持つ attachment. 色の帯を残すため attachment に入れる
+#[derive(Debug, Clone, Serialize)]
 pub struct Attachment {
-    pub title: Option<String>,
-    pub title_link: Option<url::Url>,
     pub fallback: String,
-    pub text: String,
     pub color: Option<Color>,
+    pub blocks: Vec<Block>,
 }
 
-#[derive(Debug, Serialize)]
+// Block Kit の上限
+const HEADER_MAX_CHARS: usize = 150;
+const TEXT_MAX_CHARS: usize = 3000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Header { text: Text },
+    Section { text: Text },
+    Context { elements: Vec<ContextElement> },
+    Divider,
+    Actions { elements: Vec<ActionElement> },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    PlainText { text: String, emoji: bool },
+    Mrkdwn { text: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContextElement {
+    Mrkdwn {
+        text: String,
+    },
+    Image {
+        image_url: url::Url,
+        alt_text: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActionElement {
+    Button { text: Text, url: url::Url },
+}
+
+impl Block {
+    pub fn header(text: &str) -> Self {
+        Block::Header {
+            text: Text::PlainText {
+                text: truncate(text, HEADER_MAX_CHARS),
+                emoji: true,
+            },
+        }
+    }
+
+    // 空の text は Slack に弾かれるので呼ぶ側で除く
+    pub fn section(text: &str) -> Self {
+        Block::Section {
+            text: Text::Mrkdwn {
+                text: truncate_mrkdwn(text, TEXT_MAX_CHARS),
+            },
+        }
+    }
+
+    pub fn button(text: &str, url: url::Url) -> Self {
+        Block::Actions {
+            elements: vec![ActionElement::Button {
+                text: Text::PlainText {
+                    text: text.to_string(),
+                    emoji: true,
+                },
+                url,
+            }],
+        }
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    text.chars().take(max - 1).collect::<String>() + "…"
+}
+
+// <url|label> や &amp; の途中で切ると markup が壊れて見えるので, その手前で切る
+fn truncate_mrkdwn(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let truncated = truncate(text, max);
+    let mut head = truncated.strip_suffix('…').unwrap_or(&truncated);
+    if let Some(open) = head.rfind('<').filter(|&i| !head[i..].contains('>')) {
+        head = &head[..open];
+    }
+    if let Some(amp) = head.rfind('&').filter(|&i| !head[i..].contains(';')) {
+        head = &head[..amp];
+    }
+    format!("{head}…")
+}
+
+// mrkdwn の制御文字
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Color {
     Good,
@@ -40,44 +193,465 @@ pub enum Color {
     Merged,
     #[serde(rename = "#CB2431")]
     Closed,
+    #[serde(rename = "#6E7781")]
+    NotPlanned,
 }
 
 impl Message {
-    pub fn from_string(text: String) -> Self {
-        Self {
-            text,
-            attachments: None,
-        }
-    }
-
-    pub async fn post_message(self, token: &str, channel: &str, username: Option<&str>) {
-        let payload = MessagePayload {
+    pub fn into_payload(self, channel: &str, username: Option<&str>) -> MessagePayload {
+        MessagePayload {
             channel: channel.to_string(),
             username: username.map(|u| u.to_string()),
             text: self.text,
             fallback: None,
             attachments: self.attachments,
-        };
+            thread_ts: None,
+            reply_broadcast: false,
+        }
+    }
+
+    // 投稿済みの message を書き換える. username などは変えられない
+    pub fn into_update(self, channel_id: &str, ts: &str) -> UpdatePayload {
+        UpdatePayload {
+            channel: channel_id.to_string(),
+            ts: ts.to_string(),
+            text: self.text,
+            attachments: self.attachments,
+        }
+    }
+}
+
+impl MessagePayload {
+    pub async fn post(&self, client: &Client) -> Result<Posted, Error> {
+        call(client, "chat.postMessage", &self.channel, self).await
+    }
+}
+
+// chat.update の body. channel は名前ではなく ID でないといけない
+#[derive(Debug, Serialize)]
+pub struct UpdatePayload {
+    pub channel: String,
+    pub ts: String,
+    pub text: String,
+    pub attachments: Option<Vec<Attachment>>,
+}
+
+impl UpdatePayload {
+    pub async fn post(&self, client: &Client) -> Result<Posted, Error> {
+        call(client, "chat.update", &self.channel, self).await
+    }
+}
+
+// 一時的な失敗は exponential backoff (429 は Retry-After) で retry する
+async fn call<T: Serialize>(
+    client: &Client,
+    method: &str,
+    channel: &str,
+    payload: &T,
+) -> Result<Posted, Error> {
+    let mut attempt = 1;
+    loop {
+        let res = post(client, method, payload).await;
+        debug!("{method} to {channel} (attempt {attempt}): {res:?}");
+
+        match res {
+            Err(e) if e.is_transient() && attempt < MAX_ATTEMPTS => {
+                let wait = e.backoff(attempt);
+                warn!("{method} to {channel} failed: {e}. retrying in {wait:?}");
+                actix_web::rt::time::sleep(wait).await;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+async fn post<T: Serialize>(client: &Client, method: &str, payload: &T) -> Result<Posted, Error> {
+    let res = client
+        .http
+        .post(client.url(method))
+        .bearer_auth(&client.token)
+        .json(payload)
+        .send()
+        .await
+        .map_err(Error::Transport)?;
 
-        // post
-        let client = reqwest::Client::new();
-        let r = client
-            .post("https://slack.com/api/chat.postMessage")
-            .bearer_auth(token)
-            .json(&payload)
-            .send()
-            .await;
+    let status = res.status();
+    let retry_after = res
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs);
+    let body = res.text().await.map_err(Error::Transport)?;
 
-        debug!("{:?}", &r);
+    parse_response(status, retry_after, &body)
+}
+
+fn parse_response(
+    status: reqwest::StatusCode,
+    retry_after: Option<Duration>,
+    body: &str,
+) -> Result<Posted, Error> {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(Error::RateLimited { retry_after });
+    }
+    if !status.is_success() {
+        return Err(Error::Http(status));
+    }
+
+    let res: Response =
+        serde_json::from_str(body).map_err(|e| Error::InvalidResponse(e.to_string()))?;
+    if !res.ok {
+        let error = res.error.unwrap_or_else(|| "unknown_error".to_string());
+        if error == "ratelimited" {
+            return Err(Error::RateLimited { retry_after });
+        }
+        return Err(Error::Api(error));
+    }
+
+    match (res.channel, res.ts) {
+        (Some(channel), Some(ts)) => Ok(Posted { channel, ts }),
+        _ => Err(Error::InvalidResponse(
+            "ok response without channel or ts".to_string(),
+        )),
+    }
+}
+
+// chat.postMessage, chat.update の response
+#[derive(Debug, Deserialize)]
+struct Response {
+    ok: bool,
+    error: Option<String>,
+    channel: Option<String>,
+    ts: Option<String>,
+}
+
+// 投稿された message. channel は ID になっている
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Posted {
+    pub channel: String,
+    pub ts: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Transport(reqwest::Error),
+    Http(reqwest::StatusCode),
+    RateLimited { retry_after: Option<Duration> },
+    // ok: false. channel_not_found, not_in_channel など
+    Api(String),
+    InvalidResponse(String),
+}
+
+// Slack 側の一時的な問題を示す error
+// https://api.slack.com/methods/chat.postMessage#errors
+const TRANSIENT_API_ERRORS: &[&str] = &[
+    "internal_error",
+    "fatal_error",
+    "service_unavailable",
+    "request_timeout",
+];
 
-        if r.is_err() {
-            error!("POST: {:?}", r.err().unwrap());
+impl Error {
+    pub fn is_transient(&self) -> bool {
+        match self {
+            // 接続できなかったときだけ. timeout などは Slack が受け取っていると二重に投稿してしまう
+            Error::Transport(e) => e.is_connect(),
+            Error::Http(status) => status.is_server_error(),
+            Error::RateLimited { .. } => true,
+            Error::Api(error) => TRANSIENT_API_ERRORS.contains(&error.as_str()),
+            Error::InvalidResponse(_) => false,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        if let Error::RateLimited {
+            retry_after: Some(retry_after),
+        } = self
+        {
+            return (*retry_after).min(BACKOFF_MAX);
+        }
+        (BACKOFF_BASE * 2u32.pow(attempt - 1)).min(BACKOFF_MAX)
+    }
+
+    // metrics の label
+    pub fn reason(&self) -> &str {
+        match self {
+            Error::Transport(_) => "transport",
+            Error::Http(_) => "http",
+            Error::RateLimited { .. } => "ratelimited",
+            Error::Api(error) => error,
+            Error::InvalidResponse(_) => "invalid_response",
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Transport(e) => write!(f, "request failed: {e}"),
+            Error::Http(status) => write!(f, "unexpected HTTP status: {status}"),
+            Error::RateLimited {
+                retry_after: Some(d),
+            } => write!(f, "rate limited, retry after {d:?}"),
+            Error::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            Error::Api(error) => write!(f, "Slack API error: {error}"),
+            Error::InvalidResponse(e) => write!(f, "invalid response: {e}"),
         }
     }
 }
 
-//#[cfg(test)]
-//#[actix_web::test]
-//async fn test_post() {
-//    post_message("xoxb-***", "tmp_hubhook", "test").await;
-//}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use reqwest::StatusCode;
+
+    use crate::test_util::{client, message, mock_server};
+
+    #[test]
+    fn blocks() {
+        let attach = Attachment {
+            fallback: "title".to_string(),
+            color: Some(Color::Merged),
+            blocks: vec![
+                Block::header(&"x".repeat(200)),
+                Block::section(&format!("*<https://github.com/|{}>*", escape("a<b>&c"))),
+                Block::Divider,
+                Block::button("View", url::Url::parse("https://github.com/").unwrap()),
+            ],
+        };
+        let json = serde_json::to_value(attach).unwrap();
+        assert_eq!(json["color"], "#6F42C1");
+
+        let blocks = &json["blocks"];
+        assert_eq!(blocks[0]["type"], "header");
+        assert_eq!(blocks[0]["text"]["type"], "plain_text");
+        let header = blocks[0]["text"]["text"].as_str().unwrap();
+        assert_eq!(header.chars().count(), 150);
+        assert!(header.ends_with('…'));
+
+        assert_eq!(
+            blocks[1],
+            serde_json::json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": "*<https://github.com/|a&lt;b&gt;&amp;c>*" },
+            })
+        );
+        assert_eq!(blocks[2], serde_json::json!({ "type": "divider" }));
+        assert_eq!(blocks[3]["elements"][0]["type"], "button");
+        assert_eq!(blocks[3]["elements"][0]["url"], "https://github.com/");
+    }
+
+    #[test]
+    fn truncate_mrkdwn() {
+        let link = "<https://github.com/arkedge/hubhook|hubhook>";
+        let text = "x".repeat(TEXT_MAX_CHARS - 10) + link;
+        let Block::Section {
+            text: Text::Mrkdwn { text: truncated },
+        } = Block::section(&text)
+        else {
+            panic!("section");
+        };
+        assert_eq!(truncated, "x".repeat(TEXT_MAX_CHARS - 10) + "…");
+
+        assert_eq!(super::truncate_mrkdwn("a &amp; b", 5), "a …");
+        assert_eq!(super::truncate_mrkdwn("<a|b> c d", 7), "<a|b> …");
+        assert_eq!(super::truncate_mrkdwn("<a|b>", 5), "<a|b>");
+    }
+
+    #[test]
+    fn response() {
+        let posted = parse_response(
+            StatusCode::OK,
+            None,
+            r#"{"ok": true, "channel": "C123ABC456", "ts": "1503435956.000247", "message": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            posted,
+            Posted {
+                channel: "C123ABC456".to_string(),
+                ts: "1503435956.000247".to_string()
+            }
+        );
+
+        let e = parse_response(
+            StatusCode::OK,
+            None,
+            r#"{"ok": false, "error": "channel_not_found"}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(&e, Error::Api(error) if error == "channel_not_found"));
+        assert_eq!(e.reason(), "channel_not_found");
+        assert!(!e.is_transient());
+
+        let e = parse_response(
+            StatusCode::OK,
+            None,
+            r#"{"ok": false, "error": "internal_error"}"#,
+        )
+        .unwrap_err();
+        assert!(e.is_transient());
+
+        let e = parse_response(StatusCode::BAD_GATEWAY, None, "<html>").unwrap_err();
+        assert!(matches!(e, Error::Http(StatusCode::BAD_GATEWAY)));
+        assert!(e.is_transient());
+
+        let e = parse_response(StatusCode::OK, None, "<html>").unwrap_err();
+        assert!(matches!(e, Error::InvalidResponse(_)));
+        assert!(!e.is_transient());
+    }
+
+    #[test]
+    fn backoff() {
+        let e = parse_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some(Duration::from_secs(30)),
+            r#"{"ok": false, "error": "ratelimited"}"#,
+        )
+        .unwrap_err();
+        assert!(e.is_transient());
+        assert_eq!(e.backoff(1), Duration::from_secs(30));
+        assert_eq!(e.backoff(3), Duration::from_secs(30));
+
+        let e = Error::RateLimited {
+            retry_after: Some(Duration::from_secs(3600)),
+        };
+        assert_eq!(e.backoff(1), BACKOFF_MAX);
+
+        let e = Error::Http(StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(e.backoff(1), Duration::from_secs(1));
+        assert_eq!(e.backoff(2), Duration::from_secs(2));
+        assert_eq!(e.backoff(3), Duration::from_secs(4));
+        assert_eq!(e.backoff(10), BACKOFF_MAX);
+    }
+
+    // chat.postMessage の mock. 最初の 1 回は rate limit を返す
+    async fn mock_slack(
+        calls: actix_web::web::Data<std::sync::atomic::AtomicUsize>,
+        req: actix_web::HttpRequest,
+        body: actix_web::web::Json<serde_json::Value>,
+    ) -> actix_web::HttpResponse {
+        use actix_web::HttpResponse;
+
+        let auth = req.headers().get("authorization").unwrap();
+        assert_eq!(auth, "Bearer xoxb-test");
+
+        let n = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        match body["channel"].as_str().unwrap() {
+            _ if n == 0 => HttpResponse::TooManyRequests()
+                .insert_header(("retry-after", "0"))
+                .json(serde_json::json!({ "ok": false, "error": "ratelimited" })),
+            "general" => HttpResponse::Ok().json(serde_json::json!({
+                "ok": true,
+                "channel": "C123ABC456",
+                "ts": "1503435956.000247",
+                "message": { "text": body["text"] },
+            })),
+            _ => HttpResponse::Ok()
+                .json(serde_json::json!({ "ok": false, "error": "channel_not_found" })),
+        }
+    }
+
+    #[actix_web::test]
+    async fn post_to_mock_server() {
+        use actix_web::web;
+
+        let calls = web::Data::new(std::sync::atomic::AtomicUsize::new(0));
+        let (url, handle) = {
+            let calls = calls.clone();
+            mock_server(move |cfg| {
+                cfg.app_data(calls.clone())
+                    .route("/api/chat.postMessage", web::post().to(mock_slack));
+            })
+        };
+
+        // base URL の末尾の / は省略できる
+        let client = client(&format!("{url}/api"), Duration::from_secs(5));
+        let posted = message("hello")
+            .into_payload("general", Some("hubhook"))
+            .post(&client)
+            .await
+            .unwrap();
+        assert_eq!(posted.ts, "1503435956.000247");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // retry しない error
+        let e = message("hello")
+            .into_payload("unknown", None)
+            .post(&client)
+            .await
+            .unwrap_err();
+        assert_eq!(e.reason(), "channel_not_found");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        handle.stop(false).await;
+    }
+
+    #[actix_web::test]
+    async fn transport_errors() {
+        use actix_web::{web, HttpResponse};
+
+        // 誰も listen していない port には届いていないので retry してよい
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let e = post(
+            &client(&format!("http://{addr}/api"), Duration::from_secs(1)),
+            "chat.postMessage",
+            &message("hello").into_payload("general", None),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(&e, Error::Transport(e) if e.is_connect()));
+        assert!(e.is_transient());
+
+        // 応答の timeout は Slack が投稿済みかもしれないので retry しない
+        let calls = web::Data::new(std::sync::atomic::AtomicUsize::new(0));
+        let (url, handle) = {
+            let calls = calls.clone();
+            mock_server(move |cfg| {
+                cfg.app_data(calls.clone()).route(
+                    "/api/chat.postMessage",
+                    web::post().to(
+                        |calls: web::Data<std::sync::atomic::AtomicUsize>| async move {
+                            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            actix_web::rt::time::sleep(Duration::from_secs(2)).await;
+                            HttpResponse::Ok().json(serde_json::json!({ "ok": true }))
+                        },
+                    ),
+                );
+            })
+        };
+
+        let client = Client::new(
+            "xoxb-test".to_string(),
+            &format!("{url}/api"),
+            Duration::from_millis(200),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        let e = message("hello")
+            .into_payload("general", None)
+            .post(&client)
+            .await
+            .unwrap_err();
+        assert!(matches!(&e, Error::Transport(e) if e.is_timeout()));
+        assert!(!e.is_transient());
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        handle.stop(false).await;
+    }
+}
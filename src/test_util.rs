@@ -0,0 +1,44 @@
+// 複数の module の test で使う fixture と mock
+use std::time::Duration;
+
+use actix_web::dev::ServerHandle;
+use actix_web::{web, App, HttpServer};
+
+use crate::{github, slack};
+
+// test/ 以下に保存した webhook payload. 対応していない event は None
+pub fn try_de(event: &str, test_json: &str) -> Option<github::Payload> {
+    let payload = std::fs::read(format!("test/{test_json}")).unwrap();
+    github::Payload::from_event(event, &payload).unwrap()
+}
+
+pub fn de(event: &str, test_json: &str) -> github::Payload {
+    try_de(event, test_json).unwrap()
+}
+
+pub fn message(text: &str) -> slack::Message {
+    slack::Message {
+        text: text.to_string(),
+        attachments: None,
+    }
+}
+
+// 127.0.0.1 の空いている port で Slack API の mock を動かし, その "http://host:port" を返す
+pub fn mock_server<F>(configure: F) -> (String, ServerHandle)
+where
+    F: Fn(&mut web::ServiceConfig) + Clone + Send + 'static,
+{
+    let server = HttpServer::new(move || App::new().configure(configure.clone()))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+    (format!("http://{addr}"), handle)
+}
+
+pub fn client(base_url: &str, timeout: Duration) -> slack::Client {
+    slack::Client::new("xoxb-test".to_string(), base_url, timeout, timeout).unwrap()
+}
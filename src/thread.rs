@@ -0,0 +1,478 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tracing::error;
+
+use crate::github;
+use crate::slack::Posted;
+use crate::util::now;
+
+// 同じ issue / PR の event を最初の message の thread にまとめる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Thread {
+    // issue / PR を開いた message. ts を覚えて thread の親にする
+    Start { node_id: String },
+    // 親が分かればその thread に返信する. broadcast は channel にも流す
+    Reply { node_id: String, broadcast: bool },
+    // 状態の変化や編集. 親が分かれば新しく投稿せずに親を書き換える
+    Update { node_id: String },
+}
+
+impl Thread {
+    pub fn of(payload: &github::Payload) -> Option<Self> {
+        use github::Payload;
+
+        let reply = |node_id: &str, broadcast: bool| {
+            Some(Thread::Reply {
+                node_id: node_id.to_string(),
+                broadcast,
+            })
+        };
+        let start = |node_id: &str| {
+            Some(Thread::Start {
+                node_id: node_id.to_string(),
+            })
+        };
+        let update = |node_id: &str| {
+            Some(Thread::Update {
+                node_id: node_id.to_string(),
+            })
+        };
+
+        match payload {
+            Payload::Issues(issues) => match issues.action {
+                github::IssuesAction::Opened => start(&issues.issue.node_id),
+                // 移動後は別の issue になる
+                github::IssuesAction::Transferred => None,
+                github::IssuesAction::Closed
+                | github::IssuesAction::Reopened
+                | github::IssuesAction::Edited => update(&issues.issue.node_id),
+                _ => reply(&issues.issue.node_id, false),
+            },
+            Payload::PullRequest(pr) => match pr.action {
+                github::PullRequestAction::Opened => start(&pr.pull_request.node_id),
+                // merge, close は親の色と badge で分かるようにする
+                github::PullRequestAction::Closed
+                | github::PullRequestAction::Reopened
+                | github::PullRequestAction::Edited => update(&pr.pull_request.node_id),
+                // review してほしい人に気付いてもらう
+                github::PullRequestAction::ReadyForReview => reply(&pr.pull_request.node_id, true),
+                _ => reply(&pr.pull_request.node_id, false),
+            },
+            // PR への comment でも issue.node_id は PR のものになる
+            Payload::IssueComment(ic) => reply(&ic.issue.node_id, false),
+            Payload::PullRequestReview(review) => reply(&review.pull_request.node_id, false),
+            Payload::PullRequestReviewComment(rc) => reply(&rc.pull_request.node_id, false),
+            _ => None,
+        }
+    }
+}
+
+// 親 message. chat.update には channel の名前ではなく ID が要る
+// channel_id を記録する前の DB から引き継いだ親は ID が分からないので, 返信にだけ使う
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parent {
+    pub channel_id: Option<String>,
+    pub ts: String,
+}
+
+// PRAGMA user_version で管理する threads table の版
+// 1: channel_id を追加
+const SCHEMA_VERSION: i64 = 1;
+
+// (channel, node_id) -> 親 message
+// ttl を過ぎたものと capacity を超えた古いものは忘れ, その issue / PR の event は新しく投稿する
+#[derive(Debug)]
+pub struct Threads {
+    store: Store,
+    ttl: Duration,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+enum Store {
+    Memory(Mutex<Memory>),
+    Sqlite(Mutex<rusqlite::Connection>),
+}
+
+type Key = (String, String); // (channel, node_id)
+
+#[derive(Debug, Default)]
+struct Memory {
+    parents: HashMap<Key, (Parent, u64)>,
+    order: VecDeque<(u64, Key)>, // 記録した順
+}
+
+impl Threads {
+    pub fn memory(ttl: Duration, capacity: usize) -> Self {
+        Threads {
+            store: Store::Memory(Mutex::default()),
+            ttl,
+            capacity,
+        }
+    }
+
+    pub fn sqlite(path: &Path, ttl: Duration, capacity: usize) -> rusqlite::Result<Self> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS threads (
+                channel TEXT NOT NULL,
+                node_id TEXT NOT NULL,
+                channel_id TEXT,
+                ts TEXT NOT NULL,
+                at INTEGER NOT NULL,
+                PRIMARY KEY (channel, node_id)
+            );
+            CREATE INDEX IF NOT EXISTS threads_at ON threads (at);",
+        )?;
+        migrate(&conn)?;
+        Ok(Threads {
+            store: Store::Sqlite(Mutex::new(conn)),
+            ttl,
+            capacity,
+        })
+    }
+
+    // simulate 用. serve が使っている DB を書き換えないよう, 読み取り専用で開いて schema も触らない
+    // 読むだけなので ttl で消されていないものは全て親とみなす
+    pub fn sqlite_read_only(path: &Path) -> Result<Self, String> {
+        let conn =
+            rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+                .map_err(|e| e.to_string())?;
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if version < SCHEMA_VERSION {
+            return Err(format!(
+                "the schema is older than this hubhook (version {version} < {SCHEMA_VERSION}). \
+                 start the server once to migrate it"
+            ));
+        }
+        Ok(Threads {
+            store: Store::Sqlite(Mutex::new(conn)),
+            ttl: Duration::MAX,
+            capacity: usize::MAX,
+        })
+    }
+
+    // store が壊れていても通知は落とさず, thread にしないだけにする
+    pub fn get(&self, channel: &str, node_id: &str) -> Option<Parent> {
+        self.get_at(channel, node_id, now()).unwrap_or_else(|e| {
+            error!("could not look up the thread of {node_id} in {channel}: {e}");
+            None
+        })
+    }
+
+    // 既にある場合は最初の message を親のままにする
+    pub fn insert(&self, channel: &str, node_id: &str, posted: &Posted) {
+        if let Err(e) = self.insert_at(channel, node_id, posted, now()) {
+            error!("could not record the thread of {node_id} in {channel}: {e}");
+        }
+    }
+
+    fn expired(&self, now: u64) -> u64 {
+        now.saturating_sub(self.ttl.as_secs())
+    }
+
+    fn get_at(&self, channel: &str, node_id: &str, now: u64) -> rusqlite::Result<Option<Parent>> {
+        let expired = self.expired(now);
+        match &self.store {
+            Store::Memory(memory) => Ok(memory
+                .lock()
+                .unwrap()
+                .parents
+                .get(&(channel.to_string(), node_id.to_string()))
+                .filter(|(_, at)| *at >= expired)
+                .map(|(parent, _)| parent.clone())),
+            Store::Sqlite(conn) => {
+                let conn = conn.lock().unwrap();
+                let parent = conn.query_row(
+                    "SELECT channel_id, ts FROM threads
+                        WHERE channel = ?1 AND node_id = ?2 AND at >= ?3",
+                    rusqlite::params![channel, node_id, expired],
+                    |row| {
+                        Ok(Parent {
+                            channel_id: row.get(0)?,
+                            ts: row.get(1)?,
+                        })
+                    },
+                );
+                match parent {
+                    Ok(parent) => Ok(Some(parent)),
+                    Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+
+    fn insert_at(
+        &self,
+        channel: &str,
+        node_id: &str,
+        posted: &Posted,
+        now: u64,
+    ) -> rusqlite::Result<()> {
+        let expired = self.expired(now);
+        match &self.store {
+            Store::Memory(memory) => {
+                let mut m = memory.lock().unwrap();
+                m.evict(expired, self.capacity.saturating_sub(1));
+                let key = (channel.to_string(), node_id.to_string());
+                if !m.parents.contains_key(&key) {
+                    let parent = Parent {
+                        channel_id: Some(posted.channel.clone()),
+                        ts: posted.ts.clone(),
+                    };
+                    m.parents.insert(key.clone(), (parent, now));
+                    m.order.push_back((now, key));
+                }
+                Ok(())
+            }
+            Store::Sqlite(conn) => {
+                let conn = conn.lock().unwrap();
+                conn.execute("DELETE FROM threads WHERE at < ?1", [expired])?;
+                conn.execute(
+                    "INSERT OR IGNORE INTO threads (channel, node_id, channel_id, ts, at)
+                        VALUES (?1, ?2, ?3, ?4, ?5)",
+                    rusqlite::params![channel, node_id, posted.channel, posted.ts, now],
+                )?;
+                conn.execute(
+                    "DELETE FROM threads WHERE rowid IN
+                        (SELECT rowid FROM threads ORDER BY at DESC, rowid DESC LIMIT -1 OFFSET ?1)",
+                    [self.capacity],
+                )?;
+                Ok(())
+            }
+        }
+    }
+}
+
+// 既存の DB を今の schema に合わせる
+fn migrate(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    // channel_id の無い table は ts だけを記録していた頃のもの
+    let has_channel_id: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('threads') WHERE name = 'channel_id'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_channel_id {
+        conn.execute_batch("ALTER TABLE threads ADD COLUMN channel_id TEXT;")?;
+    }
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+}
+
+impl Memory {
+    fn evict(&mut self, expired: u64, capacity: usize) {
+        while let Some((at, key)) = self.order.front() {
+            if *at >= expired && self.parents.len() <= capacity {
+                break;
+            }
+            self.parents.remove(key);
+            self.order.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::de;
+
+    #[test]
+    fn of() {
+        let start = |node_id: &str| Thread::Start {
+            node_id: node_id.to_string(),
+        };
+        let reply = |node_id: &str, broadcast| Thread::Reply {
+            node_id: node_id.to_string(),
+            broadcast,
+        };
+        let update = |node_id: &str| Thread::Update {
+            node_id: node_id.to_string(),
+        };
+
+        let cases = [
+            ("issues", "issues_opened.json", Some(start("I_kwDO1"))),
+            ("issues", "issues_closed.json", Some(update("I_kwDO1"))),
+            (
+                "issues",
+                "issues_labeled.json",
+                Some(reply("I_kwDO1", false)),
+            ),
+            ("issues", "issues_transferred.json", None),
+            (
+                "issue_comment",
+                "issue_comment_created.json",
+                Some(reply("I_kwDO1", false)),
+            ),
+            (
+                "pull_request",
+                "pull_request_opened.json",
+                Some(start("PR_kwDO2")),
+            ),
+            (
+                "pull_request",
+                "pull_request_closed_merged.json",
+                Some(update("PR_kwDO2")),
+            ),
+            (
+                "pull_request",
+                "pull_request_ready_for_review.json",
+                Some(reply("PR_kwDO2", true)),
+            ),
+            (
+                "pull_request_review",
+                "pull_request_review_approved.json",
+                Some(reply("PR_kwDO2", false)),
+            ),
+            ("push", "push.json", None),
+        ];
+        for (event, json, expected) in cases {
+            assert_eq!(Thread::of(&de(event, json)), expected, "{json}");
+        }
+    }
+
+    fn posted(ts: &str) -> Posted {
+        Posted {
+            channel: "C1".to_string(),
+            ts: ts.to_string(),
+        }
+    }
+
+    fn check(threads: Threads) {
+        let t = &threads;
+        let ts = |channel, node_id, now| {
+            t.get_at(channel, node_id, now)
+                .unwrap()
+                .map(|parent| parent.ts)
+        };
+
+        assert_eq!(ts("dev", "I_1", 100), None);
+        t.insert_at("dev", "I_1", &posted("1.1"), 100).unwrap();
+        t.insert_at("dev", "I_1", &posted("2.2"), 101).unwrap();
+        t.insert_at("ops", "I_1", &posted("3.3"), 102).unwrap();
+        let parent = t.get_at("dev", "I_1", 102).unwrap().unwrap();
+        assert_eq!(
+            (parent.channel_id.as_deref(), parent.ts.as_str()),
+            (Some("C1"), "1.1")
+        );
+        assert_eq!(ts("ops", "I_1", 102).as_deref(), Some("3.3"));
+        assert_eq!(ts("dev", "I_2", 102), None);
+
+        // ttl (10s) を過ぎたら忘れ, 次の message が親になる
+        assert_eq!(ts("dev", "I_1", 111), None);
+        assert_eq!(ts("ops", "I_1", 111).as_deref(), Some("3.3"));
+        t.insert_at("dev", "I_1", &posted("4.4"), 111).unwrap();
+        assert_eq!(ts("dev", "I_1", 111).as_deref(), Some("4.4"));
+
+        // capacity (3) を超えたら古いものから忘れる
+        t.insert_at("dev", "I_2", &posted("5.5"), 112).unwrap();
+        t.insert_at("dev", "I_3", &posted("6.6"), 113).unwrap();
+        assert_eq!(ts("ops", "I_1", 113), None);
+        for (node_id, expected) in [("I_1", "4.4"), ("I_2", "5.5"), ("I_3", "6.6")] {
+            assert_eq!(
+                ts("dev", node_id, 113).as_deref(),
+                Some(expected),
+                "{node_id}"
+            );
+        }
+    }
+
+    #[test]
+    fn memory() {
+        check(Threads::memory(Duration::from_secs(10), 3));
+    }
+
+    #[test]
+    fn sqlite() {
+        let path = std::env::temp_dir().join(format!("hubhook-{}-threads.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        check(Threads::sqlite(&path, Duration::from_secs(10), 3).unwrap());
+
+        // 再起動後も覚えている
+        let threads = Threads::sqlite(&path, Duration::from_secs(10), 3).unwrap();
+        assert_eq!(
+            threads.get_at("dev", "I_1", 113).unwrap().unwrap().ts,
+            "4.4"
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sqlite_migration() {
+        let path = std::env::temp_dir().join(format!(
+            "hubhook-{}-threads-migration.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        // channel_id を記録する前の DB
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE threads (
+                channel TEXT NOT NULL,
+                node_id TEXT NOT NULL,
+                ts TEXT NOT NULL,
+                at INTEGER NOT NULL,
+                PRIMARY KEY (channel, node_id)
+            );
+            INSERT INTO threads (channel, node_id, ts, at) VALUES ('dev', 'I_1', '1.1', 100);",
+        )
+        .unwrap();
+        drop(conn);
+
+        // 読み取り専用では migrate せずに断る
+        let e = Threads::sqlite_read_only(&path).unwrap_err();
+        assert!(e.contains("start the server once"), "{e}");
+
+        let open = || Threads::sqlite(&path, Duration::from_secs(10), 3).unwrap();
+        let threads = open();
+        assert_eq!(
+            threads.get_at("dev", "I_1", 100).unwrap(),
+            Some(Parent {
+                channel_id: None,
+                ts: "1.1".to_string()
+            })
+        );
+        threads
+            .insert_at("dev", "I_2", &posted("2.2"), 101)
+            .unwrap();
+        assert_eq!(
+            threads
+                .get_at("dev", "I_2", 101)
+                .unwrap()
+                .unwrap()
+                .channel_id
+                .as_deref(),
+            Some("C1")
+        );
+        drop(threads);
+
+        // 2 回目以降は migrate しない
+        let threads = open();
+        assert_eq!(
+            threads.get_at("dev", "I_2", 101).unwrap().unwrap().ts,
+            "2.2"
+        );
+        drop(threads);
+
+        // 読み取り専用でも読める
+        let threads = Threads::sqlite_read_only(&path).unwrap();
+        assert_eq!(
+            threads.get_at("dev", "I_1", 101).unwrap().unwrap().ts,
+            "1.1"
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,748 @@
+#!/usr/bin/env python3
+"""Generate the webhook payload fixtures in test/ for hubhook tests.
+
+The payloads are shaped like the examples in GitHub's "Webhook events and
+payloads" documentation, with fake IDs and users. Edit this script instead of
+the JSON files, and compare it with a real delivery when a payload changes.
+
+    python3 test/gen.py                 # regenerate every fixture
+    python3 test/gen.py push.json ...   # regenerate only the given ones
+"""
+import copy, json, os, sys
+
+OUT = os.path.dirname(os.path.abspath(__file__))
+API = "https://api.github.com"
+WEB = "https://github.com"
+
+
+def user(login, id_, typ="User"):
+    u = f"{API}/users/{login}"
+    return {
+        "login": login, "id": id_, "node_id": f"MDQ6VXNlcj{id_}",
+        "avatar_url": f"https://avatars.githubusercontent.com/u/{id_}?v=4",
+        "gravatar_id": "", "url": u, "html_url": f"{WEB}/{login}",
+        "followers_url": f"{u}/followers",
+        "following_url": f"{u}/following{{/other_user}}",
+        "gists_url": f"{u}/gists{{/gist_id}}",
+        "starred_url": f"{u}/starred{{/owner}}{{/repo}}",
+        "subscriptions_url": f"{u}/subscriptions",
+        "organizations_url": f"{u}/orgs", "repos_url": f"{u}/repos",
+        "events_url": f"{u}/events{{/privacy}}",
+        "received_events_url": f"{u}/received_events",
+        "type": typ, "site_admin": False,
+    }
+
+
+ORG_LOGIN = "arkedge"
+SENDER = user("sksat", 1000001)
+ALICE = user("alice", 1000002)
+BOB = user("bob", 1000003)
+ORG_USER = user(ORG_LOGIN, 2000001, "Organization")
+
+
+def organization():
+    o = f"{API}/orgs/{ORG_LOGIN}"
+    return {
+        "login": ORG_LOGIN, "id": 2000001, "node_id": "MDEyOk9yZ2FuaXphdGlvbjI=",
+        "url": o, "repos_url": f"{o}/repos", "events_url": f"{o}/events",
+        "hooks_url": f"{o}/hooks", "issues_url": f"{o}/issues",
+        "members_url": f"{o}/members{{/member}}",
+        "public_members_url": f"{o}/public_members{{/member}}",
+        "avatar_url": "https://avatars.githubusercontent.com/u/2000001?v=4",
+        "description": "",
+    }
+
+
+def repository(name="hubhook", topics=("slack", "github")):
+    full = f"{ORG_LOGIN}/{name}"
+    r = f"{API}/repos/{full}"
+    d = {
+        "id": 3000001, "node_id": "R_kgDOGT0abc", "name": name, "full_name": full,
+        "private": False, "owner": ORG_USER, "html_url": f"{WEB}/{full}",
+        "description": "GitHub notification manager", "fork": False, "url": r,
+    }
+    for k, path in [
+        ("forks_url", "/forks"), ("keys_url", "/keys{/key_id}"),
+        ("collaborators_url", "/collaborators{/collaborator}"), ("teams_url", "/teams"),
+        ("hooks_url", "/hooks"), ("issue_events_url", "/issues/events{/number}"),
+        ("events_url", "/events"), ("assignees_url", "/assignees{/user}"),
+        ("branches_url", "/branches{/branch}"), ("tags_url", "/tags"),
+        ("blobs_url", "/git/blobs{/sha}"), ("git_tags_url", "/git/tags{/sha}"),
+        ("git_refs_url", "/git/refs{/sha}"), ("trees_url", "/git/trees{/sha}"),
+        ("statuses_url", "/statuses/{sha}"), ("languages_url", "/languages"),
+        ("stargazers_url", "/stargazers"), ("contributors_url", "/contributors"),
+        ("subscribers_url", "/subscribers"), ("subscription_url", "/subscription"),
+        ("commits_url", "/commits{/sha}"), ("git_commits_url", "/git/commits{/sha}"),
+        ("comments_url", "/comments{/number}"),
+        ("issue_comment_url", "/issues/comments{/number}"),
+        ("contents_url", "/contents/{+path}"), ("compare_url", "/compare/{base}...{head}"),
+        ("merges_url", "/merges"), ("archive_url", "/{archive_format}{/ref}"),
+        ("downloads_url", "/downloads"), ("issues_url", "/issues{/number}"),
+        ("pulls_url", "/pulls{/number}"), ("milestones_url", "/milestones{/number}"),
+        ("notifications_url", "/notifications{?since,all,participating}"),
+        ("labels_url", "/labels{/name}"), ("releases_url", "/releases{/id}"),
+        ("deployments_url", "/deployments"),
+    ]:
+        d[k] = r + path
+    d.update({
+        "created_at": "2021-10-27T05:00:55Z", "updated_at": "2024-05-01T00:00:00Z",
+        "pushed_at": "2024-05-01T00:00:00Z", "git_url": f"git://github.com/{full}.git",
+        "ssh_url": f"git@github.com:{full}.git", "clone_url": f"{WEB}/{full}.git",
+        "svn_url": f"{WEB}/{full}", "homepage": None, "size": 120,
+        "stargazers_count": 3, "watchers_count": 3, "language": "Rust",
+        "has_issues": True, "has_projects": True, "has_downloads": True,
+        "has_wiki": False, "has_pages": False, "forks_count": 0, "mirror_url": None,
+        "archived": False, "disabled": False, "open_issues_count": 2,
+        "license": {"key": "mit", "name": "MIT License", "spdx_id": "MIT",
+                    "url": f"{API}/licenses/mit", "node_id": "MDc6TGljZW5zZTEz"},
+        "allow_forking": True, "is_template": False, "topics": list(topics),
+        "visibility": "public", "forks": 0, "open_issues": 2, "watchers": 3,
+        "default_branch": "main",
+    })
+    return d
+
+
+REPO = repository()
+
+
+def label(name, id_=4000001, color="d73a4a"):
+    return {
+        "id": id_, "node_id": f"LA_kwDO{id_}", "url": f"{REPO['url']}/labels/{name}",
+        "name": name, "color": color, "default": False, "description": None,
+    }
+
+
+def reactions(url):
+    return {"url": url + "/reactions", "total_count": 0, "+1": 0, "-1": 0,
+            "laugh": 0, "hooray": 0, "confused": 0, "heart": 0, "rocket": 0, "eyes": 0}
+
+
+def issue(number=1, title="Notification is not sent", body="It does not work.",
+          labels=(), assignees=(), state="open", pr=False):
+    u = f"{REPO['url']}/issues/{number}"
+    d = {
+        "url": u, "repository_url": REPO["url"], "labels_url": u + "/labels{/name}",
+        "comments_url": u + "/comments", "events_url": u + "/events",
+        "html_url": f"{REPO['html_url']}/{'pull' if pr else 'issues'}/{number}",
+        "id": 5000000 + number, "node_id": ("PR_kwDO" if pr else "I_kwDO") + str(number),
+        "number": number, "title": title, "user": SENDER, "labels": list(labels),
+        "state": state, "locked": False,
+        "assignee": assignees[0] if assignees else None, "assignees": list(assignees),
+        "milestone": None, "comments": 0, "created_at": "2024-05-01T00:00:00Z",
+        "updated_at": "2024-05-01T00:00:00Z", "closed_at": None,
+        "author_association": "MEMBER", "active_lock_reason": None, "body": body,
+        "reactions": reactions(u), "timeline_url": u + "/timeline",
+        "performed_via_github_app": None,
+    }
+    return d
+
+
+def pull_request(number=2, title="Add review notification", body="This adds it.",
+                 labels=(), assignees=(), state="open", merged=False, draft=False,
+                 requested_reviewers=(), base="main", head="feature/review"):
+    u = f"{REPO['url']}/pulls/{number}"
+    html = f"{REPO['html_url']}/pull/{number}"
+    ref = lambda r, sha: {"label": f"{ORG_LOGIN}:{r}", "ref": r, "sha": sha,
+                          "user": ORG_USER, "repo": REPO}
+    return {
+        "url": u, "id": 6000000 + number, "node_id": f"PR_kwDO{number}", "html_url": html,
+        "diff_url": html + ".diff", "patch_url": html + ".patch",
+        "issue_url": f"{REPO['url']}/issues/{number}", "number": number,
+        "state": state, "locked": False, "title": title, "user": SENDER, "body": body,
+        "created_at": "2024-05-01T00:00:00Z", "updated_at": "2024-05-02T00:00:00Z",
+        "closed_at": "2024-05-02T00:00:00Z" if state == "closed" else None,
+        "merged_at": "2024-05-02T00:00:00Z" if merged else None,
+        "merge_commit_sha": "9f3c1b2a7d4e5f60718293a4b5c6d7e8f9012345" if merged else None,
+        "assignee": assignees[0] if assignees else None, "assignees": list(assignees),
+        "requested_reviewers": list(requested_reviewers), "requested_teams": [],
+        "labels": list(labels), "milestone": None, "draft": draft,
+        "commits_url": u + "/commits", "review_comments_url": u + "/comments",
+        "review_comment_url": f"{REPO['url']}/pulls/comments{{/number}}",
+        "comments_url": f"{REPO['url']}/issues/{number}/comments",
+        "statuses_url": f"{REPO['url']}/statuses/0123456789abcdef0123456789abcdef01234567",
+        "head": ref(head, "0123456789abcdef0123456789abcdef01234567"),
+        "base": ref(base, "fedcba9876543210fedcba9876543210fedcba98"),
+        "_links": {}, "author_association": "MEMBER", "auto_merge": None,
+        "active_lock_reason": None, "merged": merged, "mergeable": None,
+        "rebaseable": None, "mergeable_state": "unknown",
+        "merged_by": ALICE if merged else None, "comments": 0, "review_comments": 0,
+        "maintainer_can_modify": False, "commits": 1, "additions": 10, "deletions": 2,
+        "changed_files": 1,
+    }
+
+
+def envelope(**kw):
+    d = dict(kw)
+    d.update({"repository": REPO, "organization": organization(), "sender": SENDER,
+              "installation": {"id": 7000001, "node_id": "MDIzOkludGVncmF0aW9uSW5zdGFsbGF0aW9uNw=="}})
+    return d
+
+
+FIXTURES = {}
+
+
+def fixture(name):
+    def deco(f):
+        FIXTURES[name] = f
+        return f
+    return deco
+
+
+@fixture("issues_opened.json")
+def _():
+    return envelope(action="opened", issue=issue(labels=[label("bug")]))
+
+
+@fixture("issues_assigned.json")
+def _():
+    return envelope(action="assigned", issue=issue(assignees=[ALICE]), assignee=ALICE)
+
+
+# 本文などに書かれた mention は escape して通知する
+@fixture("issues_opened_escape.json")
+def _():
+    return envelope(action="opened", issue=issue(
+        title="<!here> A & B", body="<!channel> see <@U0123ABCD>", labels=[label("<!subteam>")]))
+
+
+def issue_comment(body, i=None):
+    i = i or issue()
+    url = f"{REPO['url']}/issues/comments/8000001"
+    return envelope(action="created", issue=i, comment={
+        "url": url, "html_url": f"{i['html_url']}#issuecomment-8000001",
+        "issue_url": i["url"], "id": 8000001, "node_id": "IC_kwDO8000001",
+        "user": BOB, "created_at": "2024-05-01T01:00:00Z",
+        "updated_at": "2024-05-01T01:00:00Z", "author_association": "MEMBER",
+        "body": body, "reactions": reactions(url),
+        "performed_via_github_app": None})
+
+
+@fixture("issue_comment_created.json")
+def _():
+    return issue_comment("I can reproduce this.")
+
+
+@fixture("issue_comment_escape.json")
+def _():
+    return issue_comment("<!channel> I can reproduce this.",
+                         issue(title="Crash on <!everyone>"))
+
+
+@fixture("pull_request_opened.json")
+def _():
+    return envelope(action="opened", number=2, pull_request=pull_request())
+
+
+@fixture("ping.json")
+def _():
+    return {"zen": "Keep it logically awesome.", "hook_id": 9000001,
+            "hook": {"type": "Organization", "id": 9000001, "active": True,
+                     "events": ["*"]},
+            "organization": organization(), "sender": SENDER}
+
+
+SIMPLE_PR_DROP = ("merged", "mergeable", "rebaseable", "mergeable_state", "merged_by",
+                  "comments", "review_comments", "maintainer_can_modify", "commits",
+                  "additions", "deletions", "changed_files")
+
+
+def simple_pull_request(**kw):
+    pr = pull_request(**kw)
+    for k in SIMPLE_PR_DROP:
+        del pr[k]
+    return pr
+
+
+def review(state, body, id_=9100001, user_=ALICE, number=2):
+    html = f"{REPO['html_url']}/pull/{number}"
+    return {
+        "id": id_, "node_id": f"PRR_kwDO{id_}", "user": user_, "body": body,
+        "commit_id": "0123456789abcdef0123456789abcdef01234567",
+        "submitted_at": "2024-05-02T00:00:00Z", "state": state,
+        "html_url": f"{html}#pullrequestreview-{id_}",
+        "pull_request_url": f"{REPO['url']}/pulls/{number}",
+        "author_association": "MEMBER",
+        "_links": {"html": {"href": f"{html}#pullrequestreview-{id_}"},
+                   "pull_request": {"href": f"{REPO['url']}/pulls/{number}"}},
+    }
+
+
+@fixture("pull_request_review_approved.json")
+def _():
+    return envelope(action="submitted", review=review("approved", "LGTM"),
+                    pull_request=simple_pull_request())
+
+
+@fixture("pull_request_review_changes_requested.json")
+def _():
+    return envelope(action="submitted",
+                    review=review("changes_requested", "Please add tests.", user_=BOB),
+                    pull_request=simple_pull_request())
+
+
+@fixture("pull_request_review_commented.json")
+def _():
+    return envelope(action="submitted", review=review("commented", None),
+                    pull_request=simple_pull_request())
+
+
+DIFF_HUNK = "@@ -10,6 +10,9 @@ pub struct Query {\n     title: Option<String>,\n     body: Option<String>,\n     label: Option<String>,\n+    review_state: Option<String>,"
+
+
+def review_comment(id_, body, user_=BOB, in_reply_to_id=None, number=2,
+                   diff_hunk=DIFF_HUNK):
+    html = f"{REPO['html_url']}/pull/{number}"
+    url = f"{REPO['url']}/pulls/comments/{id_}"
+    d = {
+        "url": url, "pull_request_review_id": 9100001, "id": id_,
+        "node_id": f"PRRC_kwDO{id_}",
+        "diff_hunk": diff_hunk,
+        "path": "src/main.rs", "position": 4, "original_position": 4,
+        "commit_id": "0123456789abcdef0123456789abcdef01234567",
+        "original_commit_id": "0123456789abcdef0123456789abcdef01234567",
+        "user": user_, "body": body, "created_at": "2024-05-02T00:00:00Z",
+        "updated_at": "2024-05-02T00:00:00Z",
+        "html_url": f"{html}#discussion_r{id_}",
+        "pull_request_url": f"{REPO['url']}/pulls/{number}",
+        "author_association": "MEMBER",
+        "_links": {"self": {"href": url}, "html": {"href": f"{html}#discussion_r{id_}"},
+                   "pull_request": {"href": f"{REPO['url']}/pulls/{number}"}},
+        "start_line": None, "original_start_line": None, "start_side": None,
+        "line": 13, "original_line": 13, "side": "RIGHT",
+        "reactions": reactions(url), "subject_type": "line",
+    }
+    if in_reply_to_id is not None:
+        d["in_reply_to_id"] = in_reply_to_id
+    return d
+
+
+@fixture("pull_request_review_comment_created.json")
+def _():
+    return envelope(action="created",
+                    comment=review_comment(9200001, "Should this be a `Vec`?"),
+                    pull_request=simple_pull_request())
+
+
+@fixture("pull_request_review_comment_reply.json")
+def _():
+    return envelope(action="created",
+                    comment=review_comment(9200002, "No, one state is enough.",
+                                           user_=SENDER, in_reply_to_id=9200001),
+                    pull_request=simple_pull_request())
+
+
+@fixture("pull_request_review_comment_escape.json")
+def _():
+    hunk = "@@ -1,3 +1,4 @@\n fn ok(a: bool, b: bool) -> bool {\n-    a || b\n+    a && b\n }"
+    return envelope(action="created",
+                    comment=review_comment(9200003, "<!channel> `&&` is intended", diff_hunk=hunk),
+                    pull_request=simple_pull_request())
+
+
+def milestone(number=1, title="v1.0.0"):
+    u = f"{REPO['url']}/milestones/{number}"
+    return {
+        "url": u, "html_url": f"{REPO['html_url']}/milestone/{number}",
+        "labels_url": u + "/labels", "id": 9300000 + number,
+        "node_id": f"MI_kwDO{number}", "number": number, "title": title,
+        "description": None, "creator": SENDER, "open_issues": 1, "closed_issues": 0,
+        "state": "open", "created_at": "2024-05-01T00:00:00Z",
+        "updated_at": "2024-05-01T00:00:00Z", "due_on": None, "closed_at": None,
+    }
+
+
+def closed_issue(reason):
+    i = issue(state="closed")
+    i["state_reason"] = reason
+    i["closed_at"] = "2024-05-02T00:00:00Z"
+    return i
+
+
+@fixture("issues_closed.json")
+def _():
+    return envelope(action="closed", issue=closed_issue("completed"))
+
+
+@fixture("issues_closed_not_planned.json")
+def _():
+    return envelope(action="closed", issue=closed_issue("not_planned"))
+
+
+@fixture("issues_labeled.json")
+def _():
+    return envelope(action="labeled", issue=issue(labels=[label("bug")]), label=label("bug"))
+
+
+# label 自体を削除すると label の無い unlabeled が届く
+@fixture("issues_unlabeled_deleted.json")
+def _():
+    return envelope(action="unlabeled", issue=issue())
+
+
+@fixture("issues_typed.json")
+def _():
+    return envelope(action="typed", issue=issue(),
+                    type={"id": 4100001, "node_id": "IT_kwDO4100001", "name": "Bug",
+                          "description": "An unexpected problem or behavior", "color": "red"})
+
+
+@fixture("issues_milestoned.json")
+def _():
+    i = issue()
+    i["milestone"] = milestone()
+    return envelope(action="milestoned", issue=i, milestone=milestone())
+
+
+@fixture("issues_transferred.json")
+def _():
+    global REPO
+    old = REPO
+    REPO = repository(name="hubhook-config", topics=())
+    new_repo, new_issue = REPO, issue(number=12)
+    REPO = old
+    return envelope(action="transferred", issue=issue(),
+                    changes={"new_issue": new_issue, "new_repository": new_repo})
+
+
+@fixture("issues_locked.json")
+def _():
+    i = issue()
+    i["locked"] = True
+    i["active_lock_reason"] = "resolved"
+    return envelope(action="locked", issue=i)
+
+
+def team(slug="firmware", name="Firmware"):
+    return {
+        "name": name, "id": 9400001, "node_id": "T_kwDO9400001", "slug": slug,
+        "description": "", "privacy": "closed", "notification_setting": "notifications_enabled",
+        "url": f"{API}/organizations/2000001/team/9400001",
+        "html_url": f"{WEB}/orgs/{ORG_LOGIN}/teams/{slug}",
+        "members_url": f"{API}/organizations/2000001/team/9400001/members{{/member}}",
+        "repositories_url": f"{API}/organizations/2000001/team/9400001/repos",
+        "permission": "pull", "parent": None,
+    }
+
+
+@fixture("pull_request_closed_merged.json")
+def _():
+    return envelope(action="closed", number=2,
+                    pull_request=pull_request(state="closed", merged=True))
+
+
+@fixture("pull_request_closed.json")
+def _():
+    return envelope(action="closed", number=2, pull_request=pull_request(state="closed"))
+
+
+@fixture("pull_request_reopened.json")
+def _():
+    return envelope(action="reopened", number=2, pull_request=pull_request())
+
+
+@fixture("pull_request_converted_to_draft.json")
+def _():
+    return envelope(action="converted_to_draft", number=2,
+                    pull_request=pull_request(draft=True))
+
+
+@fixture("pull_request_enqueued.json")
+def _():
+    return envelope(action="enqueued", number=2, pull_request=pull_request())
+
+
+@fixture("pull_request_ready_for_review.json")
+def _():
+    return envelope(action="ready_for_review", number=2, pull_request=pull_request())
+
+
+@fixture("pull_request_review_requested.json")
+def _():
+    return envelope(action="review_requested", number=2,
+                    pull_request=pull_request(requested_reviewers=[BOB]),
+                    requested_reviewer=BOB)
+
+
+@fixture("pull_request_review_requested_team.json")
+def _():
+    pr = pull_request()
+    pr["requested_teams"] = [team()]
+    return envelope(action="review_requested", number=2, pull_request=pr,
+                    requested_team=team())
+
+
+@fixture("pull_request_auto_merge_enabled.json")
+def _():
+    pr = pull_request()
+    pr["auto_merge"] = {"enabled_by": SENDER, "merge_method": "squash",
+                        "commit_title": "Add review notification (#2)",
+                        "commit_message": ""}
+    return envelope(action="auto_merge_enabled", number=2, pull_request=pr)
+
+
+def push_repository():
+    r = copy.deepcopy(REPO)
+    # push event だけ UNIX time になる
+    r["created_at"] = 1635310855
+    r["pushed_at"] = 1714521600
+    r["owner"]["name"] = ORG_LOGIN
+    r["owner"]["email"] = None
+    r["master_branch"] = "main"
+    r["organization"] = ORG_LOGIN
+    return r
+
+
+def commit(sha, message, author="sksat"):
+    person = {"name": author, "email": f"{author}@example.com", "username": author}
+    return {
+        "id": sha, "tree_id": "1111111111111111111111111111111111111111", "distinct": True,
+        "message": message, "timestamp": "2024-05-02T09:00:00+09:00",
+        "url": f"{REPO['html_url']}/commit/{sha}", "author": person, "committer": person,
+        "added": [], "removed": [], "modified": ["src/main.rs"],
+    }
+
+
+def push(ref, commits, before="fedcba9876543210fedcba9876543210fedcba98",
+         after=None, created=False, deleted=False, forced=False):
+    after = after or (commits[-1]["id"] if commits else "0" * 40)
+    d = envelope(**{
+        "ref": ref, "before": before, "after": after, "created": created,
+        "deleted": deleted, "forced": forced, "base_ref": None,
+        "compare": f"{REPO['html_url']}/compare/{before[:12]}...{after[:12]}",
+        "commits": commits, "head_commit": commits[-1] if commits else None,
+        "pusher": {"name": "sksat", "email": "sksat@example.com"},
+    })
+    d["repository"] = push_repository()
+    return d
+
+
+PUSH_COMMITS = [commit(f"{i:040x}".replace("0", "a", 1), f"commit {i}\n\ndetails") for i in range(1, 13)]
+
+
+@fixture("push.json")
+def _():
+    return push("refs/heads/main", PUSH_COMMITS[:2])
+
+
+@fixture("push_escape.json")
+def _():
+    return push("refs/heads/main",
+                [commit("b" * 40, "Return Option<&str> from <!channel> parser", author="A & B")])
+
+
+@fixture("push_many.json")
+def _():
+    return push("refs/heads/release/v1", PUSH_COMMITS, forced=True)
+
+
+@fixture("push_deleted.json")
+def _():
+    return push("refs/heads/feature/review", [], deleted=True)
+
+
+@fixture("push_tag.json")
+def _():
+    return push("refs/tags/v1.0.0", [], before="0" * 40,
+                after="0123456789abcdef0123456789abcdef01234567", created=True)
+
+
+RELEASE_NOTES = """## What's Changed
+* Notify pull_request_review events by @alice in https://github.com/arkedge/hubhook/pull/2
+* **Breaking**: drop `--debug` signature bypass by @sksat in https://github.com/arkedge/hubhook/pull/3
+
+See [the changelog](https://github.com/arkedge/hubhook/blob/main/CHANGELOG.md) for details.
+
+**Full Changelog**: https://github.com/arkedge/hubhook/compare/v0.5.0...v1.0.0"""
+
+
+def release(tag="v1.0.0", name="v1.0.0", prerelease=False, body=RELEASE_NOTES):
+    u = f"{REPO['url']}/releases/9500001"
+    return {
+        "url": u, "assets_url": u + "/assets",
+        "upload_url": f"https://uploads.github.com/repos/{REPO['full_name']}/releases/9500001/assets{{?name,label}}",
+        "html_url": f"{REPO['html_url']}/releases/tag/{tag}", "id": 9500001,
+        "author": SENDER, "node_id": "RE_kwDO9500001", "tag_name": tag,
+        "target_commitish": "main", "name": name, "draft": False,
+        "prerelease": prerelease, "created_at": "2024-05-02T00:00:00Z",
+        "published_at": "2024-05-02T00:00:00Z", "assets": [],
+        "tarball_url": f"{REPO['url']}/tarball/{tag}",
+        "zipball_url": f"{REPO['url']}/zipball/{tag}", "body": body,
+    }
+
+
+@fixture("release_published.json")
+def _():
+    return envelope(action="published", release=release())
+
+
+@fixture("release_prereleased.json")
+def _():
+    return envelope(action="prereleased",
+                    release=release(tag="v1.1.0-rc.1", name="v1.1.0-rc.1", prerelease=True))
+
+
+@fixture("release_edited.json")
+def _():
+    return envelope(action="edited", release=release(),
+                    changes={"body": {"from": "old notes"}})
+
+
+@fixture("create_tag.json")
+def _():
+    return envelope(ref="v1.0.0", ref_type="tag", master_branch="main",
+                    description="GitHub notification manager", pusher_type="user")
+
+
+@fixture("delete_branch.json")
+def _():
+    return envelope(ref="feature/review", ref_type="branch", pusher_type="user")
+
+
+HEAD_COMMIT = {
+    "id": "0123456789abcdef0123456789abcdef01234567",
+    "tree_id": "1111111111111111111111111111111111111111",
+    "message": "Add review notification", "timestamp": "2024-05-02T00:00:00Z",
+    "author": {"name": "sksat", "email": "sksat@example.com"},
+    "committer": {"name": "sksat", "email": "sksat@example.com"},
+}
+
+
+def workflow_run(conclusion, status="completed", branch="main"):
+    u = f"{REPO['url']}/actions/runs/9600001"
+    return {
+        "id": 9600001, "name": "Rust", "node_id": "WFR_kwLO9600001",
+        "head_branch": branch, "head_sha": HEAD_COMMIT["id"],
+        "path": ".github/workflows/rust.yml", "display_title": "Add review notification",
+        "run_number": 42, "event": "push", "status": status, "conclusion": conclusion,
+        "workflow_id": 9610001, "check_suite_id": 9620001,
+        "check_suite_node_id": "CS_kwDO9620001", "url": u,
+        "html_url": f"{REPO['html_url']}/actions/runs/9600001",
+        "pull_requests": [], "created_at": "2024-05-02T00:00:00Z",
+        "updated_at": "2024-05-02T00:05:00Z", "actor": SENDER, "run_attempt": 1,
+        "run_started_at": "2024-05-02T00:00:00Z", "triggering_actor": SENDER,
+        "jobs_url": u + "/jobs", "logs_url": u + "/logs",
+        "check_suite_url": f"{REPO['url']}/check-suites/9620001",
+        "artifacts_url": u + "/artifacts", "cancel_url": u + "/cancel",
+        "rerun_url": u + "/rerun", "workflow_url": f"{REPO['url']}/actions/workflows/9610001",
+        "head_commit": HEAD_COMMIT,
+    }
+
+
+def workflow():
+    return {
+        "id": 9610001, "node_id": "W_kwDO9610001", "name": "Rust",
+        "path": ".github/workflows/rust.yml", "state": "active",
+        "created_at": "2021-10-27T05:00:55Z", "updated_at": "2024-05-01T00:00:00Z",
+        "url": f"{REPO['url']}/actions/workflows/9610001",
+        "html_url": f"{REPO['html_url']}/blob/main/.github/workflows/rust.yml",
+        "badge_url": f"{REPO['html_url']}/workflows/Rust/badge.svg",
+    }
+
+
+@fixture("workflow_run_failure.json")
+def _():
+    return envelope(action="completed", workflow_run=workflow_run("failure"),
+                    workflow=workflow())
+
+
+@fixture("workflow_run_success.json")
+def _():
+    return envelope(action="completed", workflow_run=workflow_run("success"),
+                    workflow=workflow())
+
+
+def check_suite(conclusion, slug, name):
+    return {
+        "id": 9620001, "node_id": "CS_kwDO9620001", "head_branch": "main",
+        "head_sha": HEAD_COMMIT["id"], "status": "completed", "conclusion": conclusion,
+        "url": f"{REPO['url']}/check-suites/9620001",
+        "before": "fedcba9876543210fedcba9876543210fedcba98", "after": HEAD_COMMIT["id"],
+        "pull_requests": [],
+        "app": {"id": 9630001, "slug": slug, "node_id": "A_kwDO9630001", "owner": ORG_USER,
+                "name": name, "description": "", "external_url": "https://example.com",
+                "html_url": f"{WEB}/apps/{slug}", "created_at": "2020-01-01T00:00:00Z",
+                "updated_at": "2020-01-01T00:00:00Z", "permissions": {}, "events": []},
+        "created_at": "2024-05-02T00:00:00Z", "updated_at": "2024-05-02T00:05:00Z",
+        "rerequestable": True, "runs_rerequestable": True, "latest_check_runs_count": 1,
+        "check_runs_url": f"{REPO['url']}/check-suites/9620001/check-runs",
+        "head_commit": HEAD_COMMIT,
+    }
+
+
+@fixture("check_suite_timed_out.json")
+def _():
+    return envelope(action="completed",
+                    check_suite=check_suite("timed_out", "buildkite", "Buildkite"))
+
+
+@fixture("check_suite_github_actions.json")
+def _():
+    return envelope(action="completed",
+                    check_suite=check_suite("failure", "github-actions", "GitHub Actions"))
+
+
+def discussion(answered=False, labels=()):
+    d = {
+        "repository_url": REPO["url"],
+        "category": {"id": 9700001, "node_id": "DIC_kwDO9700001", "repository_id": REPO["id"],
+                     "emoji": ":bulb:", "name": "RFC", "description": "Design RFCs",
+                     "created_at": "2024-01-01T00:00:00Z", "updated_at": "2024-01-01T00:00:00Z",
+                     "slug": "rfc", "is_answerable": True},
+        "answer_html_url": None, "answer_chosen_at": None, "answer_chosen_by": None,
+        "html_url": f"{REPO['html_url']}/discussions/3", "id": 9710003,
+        "node_id": "D_kwDO9710003", "number": 3, "title": "RFC: thread follow-up events",
+        "user": SENDER, "labels": list(labels), "state": "open", "state_reason": None,
+        "locked": False, "comments": 0, "created_at": "2024-05-01T00:00:00Z",
+        "updated_at": "2024-05-01T00:00:00Z", "author_association": "MEMBER",
+        "active_lock_reason": None, "body": "Post follow-ups as thread replies.",
+        "reactions": reactions(f"{REPO['url']}/discussions/3"),
+        "timeline_url": f"{REPO['url']}/discussions/3/timeline",
+    }
+    if answered:
+        d["answer_html_url"] = f"{d['html_url']}#discussioncomment-9720001"
+        d["answer_chosen_at"] = "2024-05-03T00:00:00Z"
+        d["answer_chosen_by"] = SENDER
+    return d
+
+
+def discussion_comment(id_=9720001, body="Sounds good to me.", user_=BOB):
+    d = discussion()
+    return {
+        "id": id_, "node_id": f"DC_kwDO{id_}",
+        "html_url": f"{d['html_url']}#discussioncomment-{id_}", "parent_id": None,
+        "child_comment_count": 0, "repository_url": REPO["full_name"],
+        "discussion_id": d["id"], "author_association": "MEMBER", "user": user_,
+        "created_at": "2024-05-02T00:00:00Z", "updated_at": "2024-05-02T00:00:00Z",
+        "body": body, "reactions": reactions(f"{REPO['url']}/discussions/comments/{id_}"),
+    }
+
+
+@fixture("discussion_created.json")
+def _():
+    return envelope(action="created", discussion=discussion(labels=[label("rfc")]))
+
+
+@fixture("discussion_answered.json")
+def _():
+    return envelope(action="answered", discussion=discussion(answered=True),
+                    answer=discussion_comment())
+
+
+@fixture("discussion_comment_created.json")
+def _():
+    return envelope(action="created", comment=discussion_comment(),
+                    discussion=discussion())
+
+
+def main(names):
+    os.makedirs(OUT, exist_ok=True)
+    for name, f in FIXTURES.items():
+        if names and name not in names:
+            continue
+        with open(os.path.join(OUT, name), "w") as fp:
+            json.dump(f(), fp, indent=2, ensure_ascii=False)
+            fp.write("\n")
+
+
+if __name__ == "__main__":
+    main(sys.argv[1:])